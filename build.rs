@@ -0,0 +1,117 @@
+// Compiles every shader in `shaders/` to SPIR-V and generates `$OUT_DIR/shaders.rs`, with one
+// module per shader equivalent to an inline `vulkano_shaders::shader!` block. The module of
+// `shaders/triangle.vert` is `shaders::triangle_vert`.
+//
+// The stage comes from the extension (.vert, .frag, .comp, .geom, .tesc, .tese) or, for .glsl
+// files, from a `#pragma shader_stage(...)` line. Files in `shaders/include/` are only used
+// through `#include "..."`, which is resolved relative to the including file and then to
+// `shaders/include/`. Extra macros can be defined with `VULKANO_GUIDE_SHADER_DEFINES=A=1,B`.
+
+use shaderc::Compiler;
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Stage parsing and `#include` resolution, shared with the runtime loader in `shader_loader`.
+#[path = "src/shader_stage.rs"]
+mod shader_stage;
+
+use shader_stage::{compile_options, declared_stage, entry_point_stages};
+
+fn module_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+fn shader_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            if path.file_name().map_or(false, |name| name != "include") {
+                shader_files(&path, files);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+fn main() {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("shaders");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-changed={}", root.display());
+    println!("cargo:rerun-if-changed=src/shader_stage.rs");
+    println!("cargo:rerun-if-env-changed=VULKANO_GUIDE_SHADER_DEFINES");
+
+    let mut compiler = Compiler::new().expect("failed to create the shader compiler");
+    let mut options = compile_options(root.join("include")).expect("failed to create the shader compiler options");
+
+    if let Ok(defines) = env::var("VULKANO_GUIDE_SHADER_DEFINES") {
+        for define in defines.split(',').map(|define| define.trim()).filter(|define| !define.is_empty()) {
+            let mut parts = define.splitn(2, '=');
+            options.add_macro_definition(parts.next().unwrap(), parts.next());
+        }
+    }
+
+    let mut files = Vec::new();
+    shader_files(&root, &mut files);
+
+    let mut generated = String::new();
+    let mut errors = Vec::new();
+
+    for path in files {
+        let source = fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+
+        let stage = match declared_stage(&source, &path) {
+            Ok(stage) => stage,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            },
+        };
+
+        let artifact = match compiler.compile_into_spirv(&source, stage.shaderc_kind(), &path.to_string_lossy(), "main",
+                                                         Some(&options)) {
+            Ok(artifact) => artifact,
+            Err(e) => {
+                errors.push(format!("{} ({:?}): {}", path.display(), stage, e));
+                continue;
+            },
+        };
+        if artifact.get_num_warnings() > 0 {
+            for warning in artifact.get_warning_messages().lines() {
+                println!("cargo:warning={}", warning);
+            }
+        }
+
+        let stages = entry_point_stages(artifact.as_binary());
+        if stages != [stage] {
+            errors.push(format!("{}: declared as {:?} but its entry points are {:?}", path.display(), stage, stages));
+            continue;
+        }
+
+        let name = module_name(&path);
+        let spirv = out_dir.join(format!("{}.spv", name));
+        fs::write(&spirv, artifact.as_binary_u8()).unwrap();
+
+        writeln!(generated, "pub mod {} {{", name).unwrap();
+        writeln!(generated, "    vulkano_shaders::shader! {{ bytes: {:?} }}", spirv.to_string_lossy()).unwrap();
+        writeln!(generated, "}}").unwrap();
+    }
+
+    if !errors.is_empty() {
+        panic!("failed to build the shaders:\n{}", errors.join("\n"));
+    }
+
+    fs::write(out_dir.join("shaders.rs"), generated).unwrap();
+}
@@ -4,8 +4,6 @@ use vulkano::instance::PhysicalDevice;
 use vulkano::device::Device;
 use vulkano::device::DeviceExtensions;
 use vulkano::device::Features;
-use vulkano::buffer::BufferUsage;
-use vulkano::buffer::CpuAccessibleBuffer;
 use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::command_buffer::CommandBuffer;
 use vulkano::sync::GpuFuture;
@@ -14,7 +12,7 @@ use vulkano::image::Dimensions;
 use vulkano::image::StorageImage;
 use vulkano::format::ClearValue;
 
-use image::{ImageBuffer, Rgba};
+use vulkano_guide::readback;
 
 fn main() {
 
@@ -58,24 +56,16 @@ fn main() {
     let image = StorageImage::new(device.clone(), Dimensions::Dim2d { width: 1024, height: 1024},
                     Format::R8G8B8A8Unorm, Some(queue.family())).unwrap();
 
-    let iter = (0 .. 1024 * 1024 * 4).map(|_| 0u8);
-    let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, iter).expect("failed to create buffer");
-
     let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
     builder
-        .clear_color_image(image.clone(), ClearValue::Float([0.0, 0.0, 1.0, 1.0])).unwrap()
-        .copy_image_to_buffer(image.clone(), buf.clone()).unwrap();
+        .clear_color_image(image.clone(), ClearValue::Float([0.0, 0.0, 1.0, 1.0])).unwrap();
         
     let command_buffer = builder.build().unwrap();
 
     let finished = command_buffer.execute(queue.clone()).unwrap();
     finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
 
-    let buffer_content = buf.read().unwrap();
-
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
-
-    image.save("image.png").unwrap();
+    readback::save_image(device.clone(), queue.clone(), image.clone(), "image.png").unwrap();
 
 }
 
@@ -0,0 +1,96 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+
+use vulkano_guide::cpu_reference::{self, MAX_MISMATCH};
+use vulkano_guide::fractal::{Fractal, FractalRenderer, Precision, View};
+use vulkano_guide::palette::Coloring;
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+
+use std::time::Instant;
+
+fn main() {
+
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
+
+    let physical = PhysicalDevice::enumerate(&instance).next().expect("no device available");
+    println!("Selected device: {}", physical.name());
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_compute())
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+
+        let device_ext = DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            .. DeviceExtensions::none()
+        };
+
+        let features = Features {
+            shader_float64: physical.supported_features().shader_float64,
+            .. Features::none()
+        };
+
+        Device::new(physical, &features, &device_ext,
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
+    let mut precisions = vec![Precision::Single];
+    if device.enabled_features().shader_float64 {
+        precisions.push(Precision::Double);
+    }
+
+    let cases = vec![
+        ("mandelbrot", View::new(Fractal::Mandelbrot)),
+        ("seahorse valley", View { center: [-0.7435, 0.1314], scale: 0.01, max_iterations: 1000, .. View::new(Fractal::Mandelbrot) }),
+        ("julia", View::new(Fractal::Julia { c: [-0.8, 0.156] })),
+        ("burning ship", View::new(Fractal::BurningShip)),
+        ("tricorn", View::new(Fractal::Tricorn)),
+        ("multibrot", View::new(Fractal::Multibrot { exponent: 3.0 })),
+    ];
+
+    let coloring = Coloring::default();
+    let mut failures = 0;
+
+    for &precision in precisions.iter() {
+        let mut renderer = FractalRenderer::new(device.clone(), queue.clone(), [16, 16, 1], precision, Some(pipeline_cache.cache()));
+        renderer.set_coloring(coloring.clone());
+
+        for (name, view) in cases.iter() {
+            let view = View { width: 512, height: 512, .. *view };
+
+            let start = Instant::now();
+            let gpu = renderer.render(&view);
+            let gpu_time = start.elapsed();
+
+            let start = Instant::now();
+            let cpu = cpu_reference::render(&view, &coloring, precision);
+            let cpu_time = start.elapsed();
+
+            let (mismatched, diff) = cpu_reference::compare(&gpu, &cpu);
+            let fraction = mismatched as f64 / (view.width * view.height) as f64;
+            println!("{} ({:?}): {} pixels differ ({:.3}%), gpu {:?}, cpu {:?}",
+                     name, precision, mismatched, fraction * 100.0, gpu_time, cpu_time);
+
+            if fraction > MAX_MISMATCH {
+                let path = format!("diff_{}_{:?}.png", name.replace(' ', "_"), precision).to_lowercase();
+                diff.save(&path).unwrap();
+                println!("  too many differences, see {}", path);
+                failures += 1;
+            }
+        }
+    }
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    assert_eq!(failures, 0, "the GPU output doesn't match the CPU reference");
+    println!("GPU output matches the CPU reference");
+
+}
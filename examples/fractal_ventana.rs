@@ -0,0 +1,350 @@
+use vulkano::instance::{Instance,PhysicalDevice};
+use vulkano::device::{Device,DeviceExtensions,Features};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::format::Format;
+use vulkano::sampler::Filter;
+use vulkano::sync;
+use vulkano::sync::{GpuFuture,FlushError};
+use vulkano::image::ImageUsage;
+use vulkano::swapchain;
+use vulkano::swapchain::{Swapchain, SurfaceTransform, PresentMode, ColorSpace, FullscreenExclusive,
+                        SwapchainCreationError, AcquireError};
+
+use vulkano_win::VkSurfaceBuild;
+
+use winit::event_loop::{ControlFlow,EventLoop};
+use winit::window::WindowBuilder;
+use winit::event::{Event, WindowEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode};
+
+use vulkano_guide::cli::Args;
+use vulkano_guide::fractal::{FractalRenderer, Precision, View};
+use vulkano_guide::palette::{Coloring, Palette};
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::supersampling::Supersampling;
+use vulkano_guide::tiles::{self, TilePlan};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Drag with the left button to pan, scroll to zoom at the cursor, Up/Down change the iteration
+// count, P cycles the palettes, S toggles smooth coloring, R resets the view and E exports the
+// current view at `--export-scale` times the window size (4 by default), supersampled with
+// `--supersampling n`.
+fn main() {
+
+    let args = Args::from_env();
+    let mut view = View::from_args(&args);
+    let mut coloring = Coloring::from_args(&args);
+    let export_scale = args.value::<u32>("--export-scale").unwrap_or(4);
+    let export_supersampling = Supersampling::from_args(&args);
+
+    let instance = {
+        let extensions = vulkano_win::required_extensions();
+        Instance::new(None, &extensions, None).expect("failed to create an instance")
+    };
+
+    let mut iter = PhysicalDevice::enumerate(&instance);
+    let physical = iter.next().expect("no device available");
+
+    let events_loop = EventLoop::new();
+    let surface = WindowBuilder::new().build_vk_surface(&events_loop, instance.clone()).unwrap();
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_graphics() && q.supports_compute() && surface.is_supported(q).unwrap_or(false))
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+        let device_ext = DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            khr_swapchain: true,
+            .. DeviceExtensions::none()
+        };
+
+        let features = Features {
+            shader_float64: physical.supported_features().shader_float64,
+            .. Features::none()
+        };
+
+        Device::new(physical, &features, &device_ext,
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    let caps = surface.capabilities(physical)
+                .expect("failed to get surface capabilities");
+
+    let dimensions: [u32; 2] = surface.window().inner_size().into();
+    let alpha = caps.supported_composite_alpha.iter().next().unwrap();
+    // The fractal's rgba8 image already holds sRGB encoded values, a blit into an sRGB swapchain
+    // would encode them a second time. A UNORM swapchain takes the bytes as they are.
+    let format = caps.supported_formats.iter()
+        .map(|&(format, _)| format)
+        .find(|&format| format == Format::B8G8R8A8Unorm || format == Format::R8G8B8A8Unorm)
+        .unwrap_or_else(|| {
+            println!("Warning: the surface has no UNORM format, colors will look washed out");
+            caps.supported_formats[0].0
+        });
+
+    // The fractal is rendered into a storage image and blitted to the swapchain, so the swapchain
+    // images are only ever transfer destinations.
+    if !caps.supported_usage_flags.transfer_destination {
+        panic!("the surface doesn't allow blitting to swapchain images");
+    }
+    let usage = ImageUsage {
+        transfer_destination: true,
+        .. ImageUsage::none()
+    };
+
+    let (mut swapchain, mut images) = Swapchain::new(
+                                device.clone(),
+                                surface.clone(),
+                                caps.min_image_count,
+                                format,
+                                dimensions,
+                                1,
+                                usage,
+                                &queue,
+                                SurfaceTransform::Identity,
+                                alpha,
+                                PresentMode::Fifo,
+                                FullscreenExclusive::Default,
+                                true,
+                                ColorSpace::SrgbNonLinear
+                            ).expect("failed to create swapchain");
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
+    let mut single = FractalRenderer::new(device.clone(), queue.clone(), [16, 16, 1], Precision::Single, Some(pipeline_cache.cache()));
+    let mut double = if device.enabled_features().shader_float64 {
+        Some(FractalRenderer::new(device.clone(), queue.clone(), [16, 16, 1], Precision::Double, Some(pipeline_cache.cache())))
+    } else {
+        None
+    };
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    single.set_coloring(coloring.clone());
+    if let Some(ref mut double) = double {
+        double.set_coloring(coloring.clone());
+    }
+
+    view.width = dimensions[0];
+    view.height = dimensions[1];
+    let mut image = single.create_image(&view);
+
+    let palettes = Palette::builtin_names();
+    let mut palette_index = 0;
+
+    let mut cursor = [0.0f64, 0.0];
+    let mut dragging = false;
+    let mut dirty = true;
+
+    let mut recreate_swapchain = false;
+
+    let mut previous_frame_end = Some(sync::now(device.clone()).boxed());
+
+    events_loop.run(move |event, _, control_flow| {
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = ControlFlow::Exit;
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Resized(_),
+                ..
+            } => {
+                recreate_swapchain = true;
+            },
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                if dragging {
+                    let pixel_size = view.pixel_size();
+                    view.center[0] -= (position.x - cursor[0]) * pixel_size;
+                    view.center[1] += (position.y - cursor[1]) * pixel_size;
+                    dirty = true;
+                }
+                cursor = [position.x, position.y];
+            },
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state, button: MouseButton::Left, .. },
+                ..
+            } => {
+                dragging = state == ElementState::Pressed;
+            },
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y as f64,
+                    MouseScrollDelta::PixelDelta(position) => position.y / 40.0,
+                };
+                let factor = 1.25f64.powf(steps);
+
+                // Keep the point under the cursor in place.
+                let anchor = view.pixel_to_complex(cursor[0], cursor[1]);
+                view.center = [
+                    anchor[0] + (view.center[0] - anchor[0]) / factor,
+                    anchor[1] + (view.center[1] - anchor[1]) / factor,
+                ];
+                view.scale /= factor;
+                dirty = true;
+            },
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. },
+                    ..
+                },
+                ..
+            } => {
+                match key {
+                    VirtualKeyCode::Up => {
+                        view.max_iterations = (view.max_iterations as f32 * 1.5) as u32;
+                    },
+                    VirtualKeyCode::Down => {
+                        view.max_iterations = ((view.max_iterations as f32 / 1.5) as u32).max(16);
+                    },
+                    VirtualKeyCode::P => {
+                        palette_index = (palette_index + 1) % palettes.len();
+                        coloring.palette = Palette::builtin(palettes[palette_index]).unwrap();
+                    },
+                    VirtualKeyCode::S => {
+                        coloring.smooth = !coloring.smooth;
+                    },
+                    VirtualKeyCode::R => {
+                        view.center = view.fractal.default_center();
+                        view.scale = view.fractal.default_scale();
+                    },
+                    VirtualKeyCode::E => {
+                        let deep = Precision::for_view(&view) == Precision::Double;
+                        let renderer = match double {
+                            Some(ref mut double) if deep => double,
+                            _ => &mut single,
+                        };
+                        renderer.set_supersampling(export_supersampling);
+                        export(&device, renderer, &view, export_scale);
+                        renderer.set_supersampling(Supersampling::Off);
+                    },
+                    VirtualKeyCode::Escape => {
+                        *control_flow = ControlFlow::Exit;
+                    },
+                    _ => (),
+                }
+
+                single.set_coloring(coloring.clone());
+                if let Some(ref mut double) = double {
+                    double.set_coloring(coloring.clone());
+                }
+                dirty = true;
+            },
+            Event::RedrawEventsCleared => {
+                previous_frame_end.as_mut().unwrap().cleanup_finished();
+
+                if recreate_swapchain {
+                    let dimensions: [u32;2] = surface.window().inner_size().into();
+                    let (new_swapchain, new_images) =
+                        match swapchain.recreate_with_dimensions(dimensions) {
+                            Ok(r) => r,
+                            Err(SwapchainCreationError::UnsupportedDimensions) => return,
+                            Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+                        };
+
+                    swapchain = new_swapchain;
+                    images = new_images;
+
+                    view.width = dimensions[0];
+                    view.height = dimensions[1];
+                    image = single.create_image(&view);
+                    dirty = true;
+                    recreate_swapchain = false;
+                }
+
+                let (image_num, suboptimal, acquire_future) =
+                    match swapchain::acquire_next_image(swapchain.clone(), None) {
+                        Ok(r) => r,
+                        Err(AcquireError::OutOfDate) => {
+                            recreate_swapchain = true;
+                            return;
+                        },
+                        Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                    };
+
+                if suboptimal {
+                    recreate_swapchain = true;
+                }
+
+                let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(
+                    device.clone(),
+                    queue.family(),
+                ).unwrap();
+
+                if dirty {
+                    let renderer = match double {
+                        Some(ref double) if Precision::for_view(&view) == Precision::Double => double,
+                        _ => &single,
+                    };
+                    renderer.dispatch(&mut builder, image.clone(), &view);
+
+                    surface.window().set_title(&format!(
+                        "center {:.17}, {:.17}  zoom {:.3e}  iterations {}  {:?} precision",
+                        view.center[0], view.center[1], view.zoom(), view.max_iterations, renderer.precision()));
+                    dirty = false;
+                }
+
+                let size = [view.width as i32, view.height as i32, 1];
+                builder
+                    .blit_image(
+                        image.clone(), [0, 0, 0], size, 0, 0,
+                        images[image_num].clone(), [0, 0, 0], size, 0, 0,
+                        1, Filter::Nearest,
+                    )
+                    .unwrap();
+
+                let command_buffer = builder.build().unwrap();
+
+                let future = previous_frame_end
+                    .take()
+                    .unwrap()
+                    .join(acquire_future)
+                    .then_execute(queue.clone(), command_buffer)
+                    .unwrap()
+                    .then_swapchain_present(queue.clone(), swapchain.clone(), image_num)
+                    .then_signal_fence_and_flush();
+
+                match future {
+                    Ok(future) => {
+                        previous_frame_end = Some(future.boxed());
+                    },
+                    Err(FlushError::OutOfDate) => {
+                        recreate_swapchain = true;
+                        previous_frame_end = Some(sync::now(device.clone()).boxed());
+                    },
+                    Err(e) => {
+                        println!("Failed to flush future: {:?}", e);
+                        previous_frame_end = Some(sync::now(device.clone()).boxed());
+                    }
+                }
+            }
+            _ => ()
+        }
+    });
+}
+
+// Renders `view` at `scale` times its size in tiles, so the export isn't limited by the device.
+fn export(device: &Device, renderer: &FractalRenderer, view: &View, scale: u32) {
+    let view = View {
+        width: view.width * scale,
+        height: view.height * scale,
+        .. *view
+    };
+
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let path = format!("fractal_{}.png", seconds);
+
+    let plan = TilePlan::for_device(device, view.width, view.height, 256 * 1024 * 1024);
+    tiles::render_to_png(&plan, &path, |tile| renderer.render(&view.tile(tile)).into_raw()).unwrap();
+    println!("Exported {}x{} to {}", view.width, view.height, path);
+}
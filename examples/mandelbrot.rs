@@ -4,24 +4,35 @@ use vulkano::instance::PhysicalDevice;
 use vulkano::device::Device;
 use vulkano::device::DeviceExtensions;
 use vulkano::device::Features;
-use vulkano::buffer::BufferUsage;
-use vulkano::buffer::CpuAccessibleBuffer;
 use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::command_buffer::CommandBuffer;
 use vulkano::sync::GpuFuture;
-use vulkano::format::Format;
-use vulkano::image::Dimensions;
-use vulkano::image::StorageImage;
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
-use vulkano::descriptor::pipeline_layout::PipelineLayoutAbstract;
-use vulkano::pipeline::ComputePipeline;
 
-use image::{ImageBuffer, Rgba};
-
-use std::sync::Arc;
+use vulkano_guide::animation::{AnimationFormat, AnimationWriter, ZoomPath};
+use vulkano_guide::cli::Args;
+use vulkano_guide::deep_zoom::{DeepView, DeepZoomRenderer};
+use vulkano_guide::export::{self, ExportImage};
+use vulkano_guide::histogram;
+use vulkano_guide::fractal::{FractalRenderer, Precision, View};
+use vulkano_guide::palette::Coloring;
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::readback::ImageDownload;
+use vulkano_guide::supersampling::Supersampling;
+use vulkano_guide::timing::GpuTimer;
+use vulkano_guide::tuning::Autotuner;
+
+use std::path::Path;
 
 fn main() {
 
+    let args = Args::from_env();
+    let view = View::from_args(&args);
+    let coloring = Coloring::from_args(&args);
+    let supersampling = Supersampling::from_args(&args);
+    let precision = Precision::from_args(&args, &view);
+    let output = args.value::<String>("--output").unwrap_or_else(|| "image.png".to_owned());
+    println!("Rendering {:?} (zoom {})", view, view.zoom());
+
     let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
 
     for physical_device in PhysicalDevice::enumerate(&instance) {
@@ -53,80 +64,145 @@ fn main() {
             .. DeviceExtensions::none()
         };
 
-        Device::new(physical, &Features::none(), &device_ext,
+        let features = Features {
+            shader_float64: physical.supported_features().shader_float64,
+            .. Features::none()
+        };
+
+        Device::new(physical, &features, &device_ext,
                     [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
     };
 
     let queue = queues.next().unwrap();
 
-    let image = StorageImage::new(device.clone(), Dimensions::Dim2d { width: 1024, height: 1024},
-                    Format::R8G8B8A8Unorm, Some(queue.family())).unwrap();
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
 
-    let shader = cs::Shader::load(device.clone()).expect("failed to create shader module");
+    if args.flag("--deep") {
+        let view = DeepView::from_args(&args);
 
-    let compute_pipeline = Arc::new(
-        ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None)
-                .expect("failed to create compute pipeline"));
+        let renderer = DeepZoomRenderer::new(device.clone(), queue.clone(), [16, 16, 1], Some(pipeline_cache.cache()));
+        let mut renderer = match renderer {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                println!("Can't render a deep zoom: {}", e);
+                return;
+            },
+        };
+        pipeline_cache.save().expect("failed to save pipeline cache");
+        renderer.set_coloring(coloring);
+        renderer.set_supersampling(supersampling);
+
+        let (image, report) = match renderer.render(&view) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                println!("Can't render a deep zoom: {}", e);
+                return;
+            },
+        };
+        println!("Reference orbit: {} points ({} bits, escaped: {}), glitched pixels: {}",
+                 report.reference_length, report.frac_bits, report.reference_escaped, report.glitched_pixels);
 
-    let layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap();
-    let set = Arc::new(
-        PersistentDescriptorSet::start(layout.clone())
-        .add_image(image.clone()).unwrap()
-        .build().unwrap(),
-    );
+        let image = ExportImage::from_rgba8(image.width(), image.height(), image.into_raw());
+        export::save(&image, &output, &args).unwrap();
+        return;
+    }
 
-    let iter = (0 .. 1024 * 1024 * 4).map(|_| 0u8);
-    let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, iter).expect("failed to create buffer");
+    if args.flag("--animate") {
+        let path = ZoomPath::from_args(&args, &view);
+        let frames = args.value("--frames").unwrap_or(60);
+        let fps = args.value("--fps").unwrap_or(30);
+        let output = args.value::<String>("--output").unwrap_or_else(|| "zoom.gif".to_owned());
+        let format = AnimationFormat::from_args(&args, Path::new(&output));
+
+        let views = path.views(&view, frames);
+        let precision = match args.raw("--precision") {
+            Some(_) => precision,
+            None if views.iter().any(|view| Precision::for_view(view) == Precision::Double) => Precision::Double,
+            None => Precision::Single,
+        };
 
-    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
-    builder
-        .dispatch([1024 / 8, 1024 / 8, 1], compute_pipeline.clone(), set.clone(), ()).unwrap()
-        .copy_image_to_buffer(image.clone(), buf.clone()).unwrap();
-        
-    let command_buffer = builder.build().unwrap();
+        let mut renderer = FractalRenderer::new(device.clone(), queue.clone(), [16, 16, 1], precision, Some(pipeline_cache.cache()));
+        pipeline_cache.save().expect("failed to save pipeline cache");
+        renderer.set_coloring(coloring);
+        renderer.set_supersampling(supersampling);
 
-    let finished = command_buffer.execute(queue.clone()).unwrap();
-    finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+        let mut writer = AnimationWriter::create(format, &output, view.width, view.height, frames, fps).unwrap();
+        for (frame, view) in views.iter().enumerate() {
+            writer.add_frame(renderer.render(view)).unwrap();
+            println!("Frame {} of {} (zoom {})", frame + 1, frames, view.zoom());
+        }
+        writer.finish().unwrap();
+        return;
+    }
 
-    let buffer_content = buf.read().unwrap();
+    let candidates = [[8, 8, 1], [16, 8, 1], [8, 16, 1], [16, 16, 1], [32, 8, 1], [32, 32, 1]];
 
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
+    let mut autotuner = Autotuner::load(device.clone());
+    let kernel = match precision {
+        Precision::Single => "mandelbrot",
+        Precision::Double => "mandelbrot-f64",
+    };
+    let local_size = autotuner.best(kernel, &candidates, |local_size| {
+        let renderer = FractalRenderer::new(device.clone(), queue.clone(), local_size, precision, Some(pipeline_cache.cache()));
+        let image = renderer.create_image(&view);
 
-    image.save("image.png").unwrap();
+        let mut timer = GpuTimer::new(device.clone(), queue.clone(), 1);
 
-}
+        let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+        timer.begin(&mut builder, "mandelbrot dispatch");
+        renderer.dispatch(&mut builder, image.clone(), &view);
+        timer.end(&mut builder);
+        let command_buffer = builder.build().unwrap();
 
-mod cs {
-    vulkano_shaders::shader! {
-        ty: "compute",
-        src: "
-#version 450
+        let finished = command_buffer.execute(queue.clone()).unwrap();
+        finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+        timer.report()[0].milliseconds
+    });
+    println!("Selected workgroup size: {:?}", local_size);
 
-layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+    let mut renderer = FractalRenderer::new(device.clone(), queue.clone(), local_size, precision, Some(pipeline_cache.cache()));
+    renderer.set_coloring(coloring);
+    renderer.set_supersampling(supersampling);
+    println!("Rendering with {:?} precision", renderer.precision());
+    let image = renderer.create_image(&view);
 
-layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+    let mut timer = GpuTimer::new(device.clone(), queue.clone(), 2);
 
-void main() {
-    vec2 norm_coordinates = (gl_GlobalInvocationID.xy + vec2(0.5)) / vec2(imageSize(img));
-    vec2 c = (norm_coordinates - vec2(0.5)) * 2.0 - vec2(1.0, 0.0);
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    timer.begin(&mut builder, "mandelbrot dispatch");
+    renderer.dispatch(&mut builder, image.clone(), &view);
+    timer.end(&mut builder);
 
-    vec2 z = vec2(0.0, 0.0);
-    float i;
-    for (i=0.0; i<1.0; i += 0.005) {
-        z = vec2(
-            z.x * z.x - z.y * z.y + c.x,
-            z.y * z.x + z.x * z.y + c.y
-        );
+    timer.begin(&mut builder, "copy image to buffer");
+    let download = ImageDownload::record(&mut builder, device.clone(), image.clone(), 0, 0).unwrap();
+    timer.end(&mut builder);
+    let command_buffer = builder.build().unwrap();
+
+    let finished = command_buffer.execute(queue.clone()).unwrap();
+    finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
 
-        if (length(z) > 4.0) {
-            break;
+    timer.print_report();
+
+    let stats = histogram::image_stats(device.clone(), queue.clone(), image.clone(), Some(pipeline_cache.cache()));
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    for (name, channel) in ["red", "green", "blue", "alpha"].iter().zip(stats.channels.iter()) {
+        println!("{}: min {} max {} mean {:.2} variance {:.2}", name, channel.min, channel.max, channel.mean, channel.variance);
+    }
+    stats.save_chart("histogram.png").unwrap();
+
+    export::save(&download.read().unwrap(), &output, &args).unwrap();
+
+    // Raw iteration counts and |z| as `.exr` (two float channels) or `.npy` (one file each).
+    if let Some(raw) = args.raw("--raw") {
+        let (_, data) = renderer.render_escape_data(&view);
+        if raw.to_lowercase().ends_with(".exr") {
+            data.save_exr(raw).unwrap();
+            println!("Saved escape data to {}", raw);
+        } else {
+            let [iterations, modulus] = data.save_npy(raw).unwrap();
+            println!("Saved escape data to {} and {}", iterations.display(), modulus.display());
         }
     }
 
-    vec4 to_write = vec4(vec3(i), 1.0);
-    imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
-}"
-    }
 }
-
-
@@ -14,6 +14,11 @@ use vulkano::pipeline::ComputePipeline;
 use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
 use vulkano::descriptor::PipelineLayoutAbstract;
 
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::shaders::mult_arr_comp as cs;
+use vulkano_guide::timing::GpuTimer;
+use vulkano_guide::tuning::Autotuner;
+
 fn main() {
 
     let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
@@ -57,17 +62,51 @@ fn main() {
     let data_buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, data_iter)
                         .expect("failed to create bufer");
 
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
     let shader = cs::Shader::load(device.clone()).expect("failed to create shader module");
 
-    let compute_pipeline = Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None)
-                            .expect("failed to create compute pipeline"));
+    let build_pipeline = |local_size_x: u32| {
+        let spec_consts = cs::SpecializationConstants { constant_0: local_size_x, .. Default::default() };
+        Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &spec_consts, Some(pipeline_cache.cache()))
+                    .expect("failed to create compute pipeline"))
+    };
+
+    let scratch_buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, 0 .. 65536)
+                        .expect("failed to create bufer");
+
+    let candidates = [[32, 1, 1], [64, 1, 1], [128, 1, 1], [256, 1, 1]];
+
+    let mut autotuner = Autotuner::load(device.clone());
+    let local_size = autotuner.best("mult_arr", &candidates, |local_size| {
+        let compute_pipeline = build_pipeline(local_size[0]);
+        let layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap();
+        let set = Arc::new(PersistentDescriptorSet::start(layout.clone())
+                    .add_buffer(scratch_buffer.clone()).unwrap().build().unwrap());
+
+        let mut timer = GpuTimer::new(device.clone(), queue.clone(), 1);
+
+        let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+        timer.begin(&mut builder, "mult_arr");
+        builder.dispatch([65536 / local_size[0], 1, 1], compute_pipeline.clone(), set.clone(), ()).unwrap();
+        timer.end(&mut builder);
+        let command_buffer = builder.build().unwrap();
+
+        let finished = command_buffer.execute(queue.clone()).unwrap();
+        finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+        timer.report()[0].milliseconds
+    });
+    println!("Selected workgroup size: {:?}", local_size);
+
+    let compute_pipeline = build_pipeline(local_size[0]);
+    pipeline_cache.save().expect("failed to save pipeline cache");
 
     let layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap();
     let set = Arc::new(PersistentDescriptorSet::start(layout.clone())
                 .add_buffer(data_buffer.clone()).unwrap().build().unwrap());
 
     let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
-    builder.dispatch([1024,1,1], compute_pipeline.clone(), set.clone(), ()).unwrap();
+    builder.dispatch([65536 / local_size[0], 1, 1], compute_pipeline.clone(), set.clone(), ()).unwrap();
     let command_buffer = builder.build().unwrap();
 
     let finished = command_buffer.execute(queue.clone()).unwrap();
@@ -83,24 +122,3 @@ fn main() {
 
     println!("Everything succeeded!");
 }
-
-mod cs {
-    vulkano_shaders::shader! {
-        ty: "compute",
-        src: "
-#version 450
-
-layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
-
-layout(set = 0, binding = 0) buffer Data {
-    uint data[];
-} buf;
-
-void main() {
-    uint idx = gl_GlobalInvocationID.x;
-    buf.data[idx] *= 12;
-}"
-
-    }
-}
-
@@ -0,0 +1,153 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::format::Format;
+use vulkano::image::AttachmentImage;
+use vulkano::image::ImageUsage;
+use vulkano::command_buffer::SubpassContents;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::framebuffer::Subpass;
+use vulkano::command_buffer::DynamicState;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
+
+use vulkano_guide::camera::Camera;
+use vulkano_guide::cli::Args;
+use vulkano_guide::export;
+use vulkano_guide::mesh::MeshVertex;
+use vulkano_guide::obj::ObjScene;
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::readback::ImageDownload;
+use vulkano_guide::shaders::{mesh_frag as fs, mesh_vert as vs};
+
+use std::sync::Arc;
+
+fn main() {
+
+    // `--obj model.obj` renders the model offscreen to `--output` (model.png), framed by a camera
+    // at `--yaw` and `--pitch` degrees around it.
+    let args = Args::from_env();
+    let input = args.value::<String>("--obj").expect("missing --obj");
+    let output = args.value::<String>("--output").unwrap_or_else(|| "model.png".to_owned());
+    let (width, height) = args.pair::<u32>("--size", 'x').unwrap_or((1024, 1024));
+    let yaw = args.value::<f32>("--yaw").unwrap_or(30.0);
+    let pitch = args.value::<f32>("--pitch").unwrap_or(20.0);
+
+    let scene = ObjScene::load(&input).unwrap();
+    println!("Loaded {} object(s), {} triangles", scene.meshes.len(), scene.triangle_count());
+    let (min, max) = scene.bounds().expect("the model has no vertices");
+    let camera = Camera::framing(min, max, yaw, pitch);
+
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
+
+    let physical = PhysicalDevice::enumerate(&instance).next().expect("no device available");
+    println!("Selected device: {}", physical.name());
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_graphics())
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+        Device::new(physical, &Features::none(), &DeviceExtensions::none(),
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    let meshes: Vec<_> = scene.meshes.iter().map(|mesh| mesh.upload(device.clone())).collect();
+
+    let render_pass = Arc::new(vulkano::single_pass_renderpass!(device.clone(),
+                        attachments: {
+                            color: {
+                                load: Clear,
+                                store: Store,
+                                format: Format::R8G8B8A8Srgb,
+                                samples: 1,
+                            },
+                            depth: {
+                                load: Clear,
+                                store: DontCare,
+                                format: Format::D16Unorm,
+                                samples: 1,
+                            }
+                        },
+                        pass: {
+                            color: [color],
+                            depth_stencil: {depth}
+                        }
+                ).unwrap());
+
+    let usage = ImageUsage {
+        transfer_source: true,
+        color_attachment: true,
+        .. ImageUsage::none()
+    };
+    let image = AttachmentImage::with_usage(device.clone(), [width, height], Format::R8G8B8A8Srgb, usage).unwrap();
+    let depth = AttachmentImage::transient(device.clone(), [width, height], Format::D16Unorm).unwrap();
+
+    let framebuffer = Arc::new(Framebuffer::start(render_pass.clone())
+                        .add(image.clone()).unwrap()
+                        .add(depth.clone()).unwrap()
+                        .build().unwrap()
+                    );
+
+    let dynamic_state = DynamicState {
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0 .. 1.0,
+        }]),
+        .. DynamicState::none()
+    };
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
+    let vs = vs::Shader::load(device.clone()).expect("failed to create vertex shader module");
+    let fs = fs::Shader::load(device.clone()).expect("failed to create fragment shader module");
+
+    let pipeline = Arc::new(GraphicsPipeline::start()
+            .vertex_input_single_buffer::<MeshVertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(fs.main_entry_point(), ())
+            .depth_stencil_simple_depth()
+            .blend_alpha_blending()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build_with_cache(pipeline_cache.cache())
+            .build(device.clone())
+            .unwrap()
+        );
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    let push_constants = vs::ty::Camera { view_projection: camera.view_projection(width as f32 / height as f32) };
+
+    let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family()).unwrap();
+    builder
+        .begin_render_pass(framebuffer.clone(), SubpassContents::Inline, vec![[0.1, 0.1, 0.1, 1.0].into(), 1f32.into()])
+        .unwrap();
+
+    for mesh in meshes.iter() {
+        mesh.draw(&mut builder, pipeline.clone(), &dynamic_state, (), push_constants);
+    }
+
+    builder
+        .end_render_pass()
+        .unwrap();
+    let download = ImageDownload::record(&mut builder, device.clone(), image.clone(), 0, 0).unwrap();
+
+    let command_buffer = builder.build().unwrap();
+    let finished = command_buffer.execute(queue.clone()).unwrap();
+    finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    // The background is opaque, the alpha left by blending translucent materials isn't useful.
+    export::save(&download.read().unwrap().without_alpha(), &output, &args).unwrap();
+    println!("Saved {}", output);
+
+}
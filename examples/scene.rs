@@ -0,0 +1,128 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::format::Format;
+use vulkano::image::AttachmentImage;
+use vulkano::image::ImageUsage;
+use vulkano::command_buffer::SubpassContents;
+use vulkano::command_buffer::DynamicState;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
+
+use vulkano_guide::cli::Args;
+use vulkano_guide::export;
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::readback::ImageDownload;
+use vulkano_guide::scene::{GltfScene, SceneRenderer};
+
+use std::sync::Arc;
+
+fn main() {
+
+    // `--gltf scene.gltf|scene.glb` renders the scene offscreen to `--output` (scene.png) from
+    // camera number `--camera` of the file, or framing the whole scene when it has none.
+    let args = Args::from_env();
+    let input = args.value::<String>("--gltf").expect("missing --gltf");
+    let output = args.value::<String>("--output").unwrap_or_else(|| "scene.png".to_owned());
+    let (width, height) = args.pair::<u32>("--size", 'x').unwrap_or((1024, 1024));
+
+    let scene = GltfScene::load(&input).unwrap();
+    println!("Loaded {} mesh(es), {} material(s), {} image(s)", scene.meshes.len(), scene.materials.len(), scene.images.len());
+    let camera = match args.value::<usize>("--camera") {
+        Some(i) => *scene.cameras().get(i).unwrap_or_else(|| panic!("the scene has no camera {}", i)),
+        None => scene.default_camera().expect("the scene has nothing to look at"),
+    };
+
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
+
+    let physical = PhysicalDevice::enumerate(&instance).next().expect("no device available");
+    println!("Selected device: {}", physical.name());
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_graphics())
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+        Device::new(physical, &Features::none(), &DeviceExtensions::none(),
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    let render_pass = Arc::new(vulkano::single_pass_renderpass!(device.clone(),
+                        attachments: {
+                            color: {
+                                load: Clear,
+                                store: Store,
+                                format: Format::R8G8B8A8Srgb,
+                                samples: 1,
+                            },
+                            depth: {
+                                load: Clear,
+                                store: DontCare,
+                                format: Format::D16Unorm,
+                                samples: 1,
+                            }
+                        },
+                        pass: {
+                            color: [color],
+                            depth_stencil: {depth}
+                        }
+                ).unwrap());
+
+    let usage = ImageUsage {
+        transfer_source: true,
+        color_attachment: true,
+        .. ImageUsage::none()
+    };
+    let image = AttachmentImage::with_usage(device.clone(), [width, height], Format::R8G8B8A8Srgb, usage).unwrap();
+    let depth = AttachmentImage::transient(device.clone(), [width, height], Format::D16Unorm).unwrap();
+
+    let framebuffer = Arc::new(Framebuffer::start(render_pass.clone())
+                        .add(image.clone()).unwrap()
+                        .add(depth.clone()).unwrap()
+                        .build().unwrap()
+                    );
+
+    let dynamic_state = DynamicState {
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0 .. 1.0,
+        }]),
+        .. DynamicState::none()
+    };
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
+    let (renderer, uploaded) = SceneRenderer::new(queue.clone(), &scene, render_pass.clone(), Some(pipeline_cache.cache()));
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family()).unwrap();
+    builder
+        .begin_render_pass(framebuffer.clone(), SubpassContents::Inline, vec![[0.1, 0.1, 0.1, 1.0].into(), 1f32.into()])
+        .unwrap();
+
+    renderer.draw(&mut builder, &dynamic_state, camera.view_projection(width as f32 / height as f32));
+
+    builder
+        .end_render_pass()
+        .unwrap();
+    let download = ImageDownload::record(&mut builder, device.clone(), image.clone(), 0, 0).unwrap();
+
+    let command_buffer = builder.build().unwrap();
+    uploaded
+        .then_execute(queue.clone(), command_buffer).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    // The background is opaque, the alpha left by blending translucent materials isn't useful.
+    export::save(&download.read().unwrap().without_alpha(), &output, &args).unwrap();
+    println!("Saved {}", output);
+
+}
@@ -0,0 +1,106 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::sync::GpuFuture;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::StorageImage;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::PipelineLayoutAbstract;
+
+use image::{ImageBuffer, Rgba};
+
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::shader_loader::RuntimeShader;
+
+use std::env;
+use std::sync::Arc;
+
+// Runs a compute shader read from disk that writes into a single rgba8 storage image at binding 0,
+// `shaders/mandelbrot.comp` by default: `cargo run --example shader_runtime -- path/to/shader.comp`.
+// Editing the shader doesn't require recompiling this example, and `#include`s are resolved like
+// in build.rs.
+fn main() {
+
+    let path = env::args().nth(1).unwrap_or_else(|| "shaders/mandelbrot.comp".to_owned());
+
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
+
+    let mut iter = PhysicalDevice::enumerate(&instance);
+
+    let physical = iter.next().expect("no device available");
+    println!("Selected device: {}", physical.name());
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_graphics() & q.supports_compute())
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+
+        let device_ext = DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            .. DeviceExtensions::none()
+        };
+
+        Device::new(physical, &Features::none(), &device_ext,
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    let image = StorageImage::new(device.clone(), Dimensions::Dim2d { width: 1024, height: 1024},
+                    Format::R8G8B8A8Unorm, Some(queue.family())).unwrap();
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
+    let shader = match RuntimeShader::load(device.clone(), &path) {
+        Ok(shader) => shader,
+        Err(e) => {
+            println!("{}: {}", path, e);
+            return;
+        }
+    };
+    println!("Loaded {} ({:?}, local size {:?})", path, shader.stage(), shader.local_size());
+    println!("Reflected layout: {:?}", shader.layout());
+
+    let compute_pipeline = shader.compute_pipeline(device.clone(), Some(pipeline_cache.cache()))
+                            .expect("failed to create compute pipeline");
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    let layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap();
+    let set = Arc::new(
+        PersistentDescriptorSet::start(layout.clone())
+        .add_image(image.clone()).unwrap()
+        .build().unwrap(),
+    );
+
+    let iter = (0 .. 1024 * 1024 * 4).map(|_| 0u8);
+    let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, iter).expect("failed to create buffer");
+
+    let local_size = shader.local_size();
+
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    builder
+        .dispatch([(1024 + local_size[0] - 1) / local_size[0], (1024 + local_size[1] - 1) / local_size[1], 1],
+                  compute_pipeline.clone(), set.clone(), ()).unwrap()
+        .copy_image_to_buffer(image.clone(), buf.clone()).unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let finished = command_buffer.execute(queue.clone()).unwrap();
+    finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    let buffer_content = buf.read().unwrap();
+
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
+
+    image.save("image.png").unwrap();
+
+}
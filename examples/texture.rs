@@ -0,0 +1,81 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::image::ImageAccess;
+use vulkano::sync::GpuFuture;
+
+use vulkano_guide::cli::Args;
+use vulkano_guide::export::OutputFormat;
+use vulkano_guide::readback::ImageDownload;
+use vulkano_guide::texture::{self, TextureFormat};
+
+fn main() {
+
+    // `--input file` (PNG, JPEG, HDR...), `--format srgb|unorm|float`, `--mipmaps` and `--storage`
+    // to upload into a storage image instead. Every level is read back as `<output>_mip<n>.png`.
+    let args = Args::from_env();
+    let input = args.value::<String>("--input").expect("missing --input");
+    let format = args.raw("--format")
+                    .map(|name| TextureFormat::from_name(name).unwrap_or_else(|| panic!("unknown texture format `{}`", name)));
+    let mipmaps = args.flag("--mipmaps");
+    let output = args.value::<String>("--output").unwrap_or_else(|| "texture".to_owned());
+
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
+
+    let physical = PhysicalDevice::enumerate(&instance).next().expect("no device available");
+    println!("Selected device: {}", physical.name());
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_graphics() & q.supports_compute())
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+
+        let device_ext = DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            .. DeviceExtensions::none()
+        };
+
+        Device::new(physical, &Features::none(), &device_ext,
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    // The readback is chained after the upload future instead of waiting for it first.
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    let (upload, downloads) = if args.flag("--storage") {
+        let (image, upload) = texture::load_storage(queue.clone(), &input, format).unwrap();
+        println!("Uploaded {:?} as a {:?} storage image", image.dimensions(), image.format());
+
+        let download = ImageDownload::record(&mut builder, device.clone(), image.clone(), 0, 0).unwrap();
+        (upload, vec![download])
+    } else {
+        let (image, upload) = texture::load(queue.clone(), &input, format, mipmaps).unwrap();
+        println!("Uploaded {:?} as {:?} with {} mip level(s)", image.dimensions(), image.format(), image.mipmap_levels());
+
+        let downloads = (0 .. image.mipmap_levels())
+                            .map(|level| {
+                                ImageDownload::record(&mut builder, device.clone(), image.clone(), level, 0).unwrap()
+                            })
+                            .collect::<Vec<_>>();
+        (upload, downloads)
+    };
+    let command_buffer = builder.build().unwrap();
+
+    upload
+        .then_execute(queue.clone(), command_buffer).unwrap()
+        .then_signal_fence_and_flush().unwrap()
+        .wait(None).unwrap();
+
+    for (level, download) in downloads.iter().enumerate() {
+        let path = format!("{}_mip{}.png", output, level);
+        download.read().unwrap().save(&path, OutputFormat::Png).unwrap();
+        println!("Saved {}", path);
+    }
+
+}
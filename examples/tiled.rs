@@ -0,0 +1,196 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::StorageImage;
+use vulkano::command_buffer::SubpassContents;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::framebuffer::Subpass;
+use vulkano::command_buffer::DynamicState;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
+
+use vulkano_guide::cli::Args;
+use vulkano_guide::fractal::{FractalRenderer, Precision, View};
+use vulkano_guide::palette::Coloring;
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::shaders::{triangle_frag as fs, triangle_transform_vert as vs};
+use vulkano_guide::supersampling::Supersampling;
+use vulkano_guide::tiles::{self, Tile, TilePlan};
+
+use std::sync::Arc;
+
+fn main() {
+
+    let args = Args::from_env();
+    let scene = args.raw("--scene").unwrap_or("mandelbrot").to_owned();
+    let view = View::from_args(&args);
+    let memory_budget = args.value::<usize>("--memory-budget").unwrap_or(256) * 1024 * 1024;
+    let output = args.value::<String>("--output").unwrap_or_else(|| "tiled.png".to_owned());
+
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
+
+    let physical = PhysicalDevice::enumerate(&instance).next().expect("no device available");
+    println!("Selected device: {}", physical.name());
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_graphics() & q.supports_compute())
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+
+        let device_ext = DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            .. DeviceExtensions::none()
+        };
+
+        let features = Features {
+            shader_float64: physical.supported_features().shader_float64,
+            .. Features::none()
+        };
+
+        Device::new(physical, &features, &device_ext,
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    let plan = match args.value("--tile-size") {
+        Some(tile_size) => TilePlan::new(view.width, view.height, tile_size, memory_budget),
+        None => TilePlan::for_device(&device, view.width, view.height, memory_budget),
+    };
+    println!("Rendering {}x{} as {}x{} tiles of {}x{}",
+             plan.width, plan.height, plan.columns(), plan.rows(), plan.tile_width, plan.tile_height);
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
+    match scene.as_str() {
+        "mandelbrot" => {
+            let precision = Precision::from_args(&args, &view);
+            let mut renderer = FractalRenderer::new(device.clone(), queue.clone(), [16, 16, 1], precision, Some(pipeline_cache.cache()));
+            renderer.set_coloring(Coloring::from_args(&args));
+            renderer.set_supersampling(Supersampling::from_args(&args));
+            pipeline_cache.save().expect("failed to save pipeline cache");
+
+            tiles::render_to_png(&plan, &output, |tile| renderer.render(&view.tile(tile)).into_raw()).unwrap();
+        },
+        "triangle" => {
+            let vertex1 = Vertex { position: [-0.5, -0.5 ]};
+            let vertex2 = Vertex { position: [ 0.0,  0.5 ]};
+            let vertex3 = Vertex { position: [ 0.5, -0.25]};
+
+            let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                                    device.clone(),
+                                    BufferUsage::all(),
+                                    false,
+                                    vec![vertex1, vertex2, vertex3].into_iter()
+                                ).unwrap();
+
+            let render_pass = Arc::new(vulkano::single_pass_renderpass!(device.clone(),
+                                attachments: {
+                                    color: {
+                                        load: Clear,
+                                        store: Store,
+                                        format: Format::R8G8B8A8Unorm,
+                                        samples: 1,
+                                    }
+                                },
+                                pass: {
+                                    color: [color],
+                                    depth_stencil: {}
+                                }
+                        ).unwrap());
+
+            let vs = vs::Shader::load(device.clone()).expect("failed to create vertex shader module");
+            let fs = fs::Shader::load(device.clone()).expect("failed to create fragment shader module");
+
+            let pipeline = Arc::new(GraphicsPipeline::start()
+                    .vertex_input_single_buffer::<Vertex>()
+                    .vertex_shader(vs.main_entry_point(), ())
+                    .viewports_dynamic_scissors_irrelevant(1)
+                    .fragment_shader(fs.main_entry_point(), ())
+                    .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                    .build_with_cache(pipeline_cache.cache())
+                    .build(device.clone())
+                    .unwrap()
+                );
+            pipeline_cache.save().expect("failed to save pipeline cache");
+
+            tiles::render_to_png(&plan, &output, |tile| {
+                let image = StorageImage::new(device.clone(), Dimensions::Dim2d { width: tile.width, height: tile.height },
+                                Format::R8G8B8A8Unorm, Some(queue.family())).unwrap();
+
+                let iter = (0 .. tile.width * tile.height * 4).map(|_| 0u8);
+                let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, iter).expect("failed to create buffer");
+
+                let framebuffer = Arc::new(Framebuffer::start(render_pass.clone())
+                                    .add(image.clone()).unwrap()
+                                    .build().unwrap()
+                                );
+
+                let dynamic_state = DynamicState {
+                    viewports: Some(vec![Viewport {
+                        origin: [0.0, 0.0],
+                        dimensions: [tile.width as f32, tile.height as f32],
+                        depth_range: 0.0 .. 1.0,
+                    }]),
+                    .. DynamicState::none()
+                };
+
+                let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family()).unwrap();
+                builder
+                    .begin_render_pass(framebuffer, SubpassContents::Inline, vec![[0.0, 0.0, 1.0, 1.0].into()])
+                    .unwrap()
+
+                    .draw(pipeline.clone(), &dynamic_state, vertex_buffer.clone(), (), tile_transform(&plan, tile))
+                    .unwrap()
+
+                    .end_render_pass()
+                    .unwrap()
+
+                    .copy_image_to_buffer(image, buf.clone())
+                    .unwrap();
+
+                let command_buffer = builder.build().unwrap();
+                command_buffer.execute(queue.clone()).unwrap()
+                    .then_signal_fence_and_flush().unwrap()
+                    .wait(None).unwrap();
+
+                let buffer_content = buf.read().unwrap();
+                buffer_content.to_vec()
+            }).unwrap();
+        },
+        other => panic!("unknown scene `{}`", other),
+    }
+
+    println!("Saved {}", output);
+
+}
+
+// Maps normalized device coordinates of the whole image to those of `tile`, so every tile draws
+// the same scene through a viewport no larger than the tile.
+fn tile_transform(plan: &TilePlan, tile: &Tile) -> vs::ty::Transform {
+    let scale = [plan.width as f32 / tile.width as f32, plan.height as f32 / tile.height as f32];
+    let offset = [
+        (plan.width as f32 - 2.0 * tile.x as f32) / tile.width as f32 - 1.0,
+        (plan.height as f32 - 2.0 * tile.y as f32) / tile.height as f32 - 1.0,
+    ];
+
+    vs::ty::Transform { scale, offset }
+}
+
+#[derive(Default, Copy, Clone)]
+struct Vertex {
+    position: [f32;2],
+}
+
+vulkano::impl_vertex!(Vertex, position);
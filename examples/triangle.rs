@@ -4,27 +4,50 @@ use vulkano::instance::PhysicalDevice;
 use vulkano::device::Device;
 use vulkano::device::DeviceExtensions;
 use vulkano::device::Features;
-use vulkano::buffer::BufferUsage;
-use vulkano::buffer::CpuAccessibleBuffer;
 use vulkano::framebuffer::Framebuffer;
 use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::command_buffer::CommandBuffer;
-use vulkano::sync::GpuFuture;
 use vulkano::format::Format;
-use vulkano::image::Dimensions;
-use vulkano::image::StorageImage;
+use vulkano::image::AttachmentImage;
+use vulkano::image::ImageUsage;
 use vulkano::command_buffer::SubpassContents;
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::framebuffer::Subpass;
 use vulkano::command_buffer::DynamicState;
 use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
 
-use image::{ImageBuffer, Rgba};
+use vulkano_guide::cli::Args;
+use vulkano_guide::export;
+use vulkano_guide::mesh::Mesh;
+use vulkano_guide::mesh::Topology;
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::readback::ImageDownload;
+use vulkano_guide::shaders::{triangle_frag as fs, triangle_vert as vs};
+use vulkano_guide::timing::GpuTimer;
 
 use std::sync::Arc;
 
 fn main() {
 
+    let args = Args::from_env();
+    let output = args.value::<String>("--output").unwrap_or_else(|| "triangle.png".to_owned());
+
+    // `--target rgba8|rgba16|rgba16f|rgba32f` picks the render target format.
+    let format = match args.raw("--target").unwrap_or("rgba8") {
+        "rgba8" => Format::R8G8B8A8Unorm,
+        "rgba16" => Format::R16G16B16A16Unorm,
+        "rgba16f" => Format::R16G16B16A16Sfloat,
+        "rgba32f" => Format::R32G32B32A32Sfloat,
+        other => panic!("unknown render target format `{}`", other),
+    };
+
+    // `--topology triangles|strip|fan|lines|line-strip|points` draws the same three vertices
+    // as other primitives.
+    let topology = args.raw("--topology").map_or(Topology::TriangleList, |name| {
+        Topology::from_name(name).unwrap_or_else(|| panic!("unknown topology `{}`", name))
+    });
+
     let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
 
     for physical_device in PhysicalDevice::enumerate(&instance) {
@@ -66,19 +89,15 @@ fn main() {
     let vertex2 = Vertex { position: [ 0.0,  0.5 ]};
     let vertex3 = Vertex { position: [ 0.5, -0.25]};
 
-    let vertex_buffer = CpuAccessibleBuffer::from_iter(
-                            device.clone(), 
-                            BufferUsage::all(), 
-                            false, 
-                            vec![vertex1, vertex2, vertex3].into_iter()
-                        ).unwrap();
+    let mesh = Mesh::new(device.clone(), "triangle", vec![vertex1, vertex2, vertex3].into_iter(),
+                         outline_indices(topology), topology);
 
     let render_pass = Arc::new(vulkano::single_pass_renderpass!(device.clone(),
                         attachments: {
                             color: {
                                 load: Clear,
                                 store: Store,
-                                format: Format::R8G8B8A8Unorm,
+                                format: format,
                                 samples: 1,
                             }
                         },
@@ -88,11 +107,12 @@ fn main() {
                         }
                 ).unwrap());
 
-    let image = StorageImage::new(device.clone(), Dimensions::Dim2d { width: 1024, height: 1024},
-                    Format::R8G8B8A8Unorm, Some(queue.family())).unwrap();
-
-    let iter = (0 .. 1024 * 1024 * 4).map(|_| 0u8);
-    let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, iter).expect("failed to create buffer");
+    let usage = ImageUsage {
+        transfer_source: true,
+        color_attachment: true,
+        .. ImageUsage::none()
+    };
+    let image = AttachmentImage::with_usage(device.clone(), [1024, 1024], format, usage).unwrap();
 
     let framebuffer = Arc::new(Framebuffer::start(render_pass.clone())
                         .add(image.clone()).unwrap()
@@ -108,44 +128,65 @@ fn main() {
         .. DynamicState::none()
     };
 
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
     let vs = vs::Shader::load(device.clone()).expect("failed to create vertex shader module");
     let fs = fs::Shader::load(device.clone()).expect("failed to create fragment shader module");
 
     let pipeline = Arc::new(GraphicsPipeline::start()
             .vertex_input_single_buffer::<Vertex>()
             .vertex_shader(vs.main_entry_point(), ())
+            .primitive_topology(mesh.topology.primitive_topology())
             .viewports_dynamic_scissors_irrelevant(1)
             .fragment_shader(fs.main_entry_point(), ())
             .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build_with_cache(pipeline_cache.cache())
             .build(device.clone())
             .unwrap()
         );
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    let mut timer = GpuTimer::new(device.clone(), queue.clone(), 2);
 
     let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family()).unwrap();
+    timer.begin(&mut builder, "triangle render pass");
     builder
         .begin_render_pass(framebuffer.clone(), SubpassContents::Inline, vec![[0.0, 0.0, 1.0, 1.0].into()])
-        .unwrap()
+        .unwrap();
 
-        .draw(pipeline.clone(), &dynamic_state, vertex_buffer.clone(), (), ())
-        .unwrap()
+    mesh.draw(&mut builder, pipeline.clone(), &dynamic_state, (), ());
 
+    builder
         .end_render_pass()
-        .unwrap()
-
-        .copy_image_to_buffer(image.clone(), buf.clone())
         .unwrap();
-        
-    let command_buffer = builder.build().unwrap();
+    timer.end(&mut builder);
+
+    timer.begin(&mut builder, "copy image to buffer");
+    let download = ImageDownload::record(&mut builder, device.clone(), image.clone(), 0, 0).unwrap();
+    timer.end(&mut builder);
 
+    let command_buffer = builder.build().unwrap();
     let finished = command_buffer.execute(queue.clone()).unwrap();
     finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
 
-    let buffer_content = buf.read().unwrap();
+    timer.print_report();
+
+    let mut image = download.read().unwrap();
+    if args.flag("--no-alpha") {
+        image = image.without_alpha();
+    }
 
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
+    export::save(&image, &output, &args).unwrap();
 
-    image.save("triangle.png").unwrap();
+}
 
+// Line topologies trace the triangle's outline, the others cover its three vertices once.
+fn outline_indices(topology: Topology) -> &'static [u32] {
+    match topology {
+        Topology::LineList => &[0, 1, 1, 2, 2, 0],
+        Topology::LineStrip => &[0, 1, 2, 0],
+        _ => &[0, 1, 2],
+    }
 }
 
 #[derive(Default, Copy, Clone)]
@@ -154,35 +195,3 @@ struct Vertex {
 }
 
 vulkano::impl_vertex!(Vertex, position);
-
-mod vs {
-    vulkano_shaders::shader! {
-        ty: "vertex",
-        src: "
-#version 450
-
-layout(location = 0) in vec2 position;
-
-void main() {
-    gl_Position = vec4(position, 0.0, 1.0);
-}
-"
-    }
-}
-
-mod fs {
-    vulkano_shaders::shader! {
-        ty: "fragment",
-        src: "
-#version 450
-
-layout(location = 0) out vec4 f_color;
-
-void main() {
-    f_color = vec4(1.0, 0.0, 0.0, 1.0);
-}
-"
-    }
-}
-
-
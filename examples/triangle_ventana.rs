@@ -1,6 +1,5 @@
 use vulkano::instance::{Instance,PhysicalDevice};
 use vulkano::device::{Device,DeviceExtensions};
-use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
 use vulkano::framebuffer::{Framebuffer,Subpass,RenderPassAbstract,FramebufferAbstract};
 use vulkano::command_buffer::{AutoCommandBufferBuilder,DynamicState,SubpassContents};
 use vulkano::sync;
@@ -20,6 +19,10 @@ use winit::event::{Event, WindowEvent};
 
 use std::sync::Arc;
 
+use vulkano_guide::mesh::{Mesh,Topology};
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::shaders::{triangle_frag as fs, triangle_vert as vs};
+
 fn main() {
 
     let instance = {
@@ -78,12 +81,10 @@ fn main() {
     let vertex2 = Vertex { position: [ 0.0,  0.5 ]};
     let vertex3 = Vertex { position: [ 0.5, -0.25]};
 
-    let vertex_buffer = CpuAccessibleBuffer::from_iter(
-                            device.clone(), 
-                            BufferUsage::all(), 
-                            false, 
-                            vec![vertex1, vertex2, vertex3].into_iter()
-                        ).unwrap();
+    let mesh = Mesh::new(device.clone(), "triangle", vec![vertex1, vertex2, vertex3].into_iter(),
+                         &[0, 1, 2], Topology::TriangleList);
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
 
     let vs = vs::Shader::load(device.clone()).expect("failed to create vertex shader module");
     let fs = fs::Shader::load(device.clone()).expect("failed to create fragment shader module");
@@ -108,13 +109,15 @@ fn main() {
             GraphicsPipeline::start()
             .vertex_input_single_buffer::<Vertex>()
             .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
+            .primitive_topology(mesh.topology.primitive_topology())
             .viewports_dynamic_scissors_irrelevant(1)
             .fragment_shader(fs.main_entry_point(), ())
             .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build_with_cache(pipeline_cache.cache())
             .build(device.clone())
             .unwrap()
         );
+    pipeline_cache.save().expect("failed to save pipeline cache");
 
     let mut dynamic_state = DynamicState {
         line_width: None,
@@ -190,15 +193,11 @@ fn main() {
 
                 builder
                     .begin_render_pass(framebuffers[image_num].clone(), SubpassContents::Inline, clear_values)
-                    .unwrap()
-                    .draw(
-                        pipeline.clone(),
-                        &dynamic_state,
-                        vertex_buffer.clone(),
-                        (),
-                        (),
-                    )
-                    .unwrap()
+                    .unwrap();
+
+                mesh.draw(&mut builder, pipeline.clone(), &dynamic_state, (), ());
+
+                builder
                     .end_render_pass()
                     .unwrap();
 
@@ -239,36 +238,6 @@ struct Vertex {
 
 vulkano::impl_vertex!(Vertex, position);
 
-mod vs {
-    vulkano_shaders::shader! {
-        ty: "vertex",
-        src: "
-#version 450
-
-layout(location = 0) in vec2 position;
-
-void main() {
-    gl_Position = vec4(position, 0.0, 1.0);
-}
-"
-    }
-}
-
-mod fs {
-    vulkano_shaders::shader! {
-        ty: "fragment",
-        src: "
-#version 450
-
-layout(location = 0) out vec4 f_color;
-
-void main() {
-    f_color = vec4(1.0, 0.0, 0.0, 1.0);
-}
-"
-    }
-}
-
 fn window_size_dependent_setup(
     images: &[Arc<SwapchainImage<Window>>],
     render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
@@ -292,4 +261,4 @@ fn window_size_dependent_setup(
                 .build()
                 .unwrap(),) as Arc<dyn FramebufferAbstract + Send + Sync>
         }).collect::<Vec<_>>()
-}
\ No newline at end of file
+}
@@ -0,0 +1,162 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::buffer::BufferUsage;
+use vulkano::buffer::CpuAccessibleBuffer;
+use vulkano::framebuffer::Framebuffer;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::format::Format;
+use vulkano::image::Dimensions;
+use vulkano::image::StorageImage;
+use vulkano::command_buffer::SubpassContents;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::framebuffer::Subpass;
+use vulkano::command_buffer::DynamicState;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
+
+use vulkano_guide::cli::Args;
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::shaders::{triangle_frag as fs, triangle_rotate_vert as vs};
+use vulkano_guide::video::VideoWriter;
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+fn main() {
+
+    // `--output video.y4m|video.mp4|...`, `--frames n`, `--fps n` and `--size WxH`.
+    let args = Args::from_env();
+    let output = args.value::<String>("--output").unwrap_or_else(|| "triangle.y4m".to_owned());
+    let frames = args.value::<u32>("--frames").unwrap_or(120);
+    let fps = args.value::<u32>("--fps").unwrap_or(30);
+    let (width, height) = args.pair::<u32>("--size", 'x').unwrap_or((1024, 1024));
+
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).expect("failed to create an instance");
+
+    let physical = PhysicalDevice::enumerate(&instance).next().expect("no device available");
+    println!("Selected device: {}", physical.name());
+
+    let queue_family = physical.queue_families()
+        .find(|&q| q.supports_graphics() & q.supports_compute())
+        .expect("Couldn't find a queue family");
+
+    let (device, mut queues) = {
+
+        let device_ext = DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            .. DeviceExtensions::none()
+        };
+
+        Device::new(physical, &Features::none(), &device_ext,
+                    [(queue_family, 1.0)].iter().cloned()).expect("failed to create a device")
+    };
+
+    let queue = queues.next().unwrap();
+
+    let vertex1 = Vertex { position: [-0.5, -0.5 ]};
+    let vertex2 = Vertex { position: [ 0.0,  0.5 ]};
+    let vertex3 = Vertex { position: [ 0.5, -0.25]};
+
+    let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                            device.clone(),
+                            BufferUsage::all(),
+                            false,
+                            vec![vertex1, vertex2, vertex3].into_iter()
+                        ).unwrap();
+
+    let render_pass = Arc::new(vulkano::single_pass_renderpass!(device.clone(),
+                        attachments: {
+                            color: {
+                                load: Clear,
+                                store: Store,
+                                format: Format::R8G8B8A8Unorm,
+                                samples: 1,
+                            }
+                        },
+                        pass: {
+                            color: [color],
+                            depth_stencil: {}
+                        }
+                ).unwrap());
+
+    // A storage image, as the tiled example renders into, so the video writer can read the frame
+    // from a compute shader as well as copy it out.
+    let image = StorageImage::new(device.clone(), Dimensions::Dim2d { width, height },
+                    Format::R8G8B8A8Unorm, Some(queue.family())).unwrap();
+
+    let framebuffer = Arc::new(Framebuffer::start(render_pass.clone())
+                        .add(image.clone()).unwrap()
+                        .build().unwrap()
+                    );
+
+    let dynamic_state = DynamicState {
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0 .. 1.0,
+        }]),
+        .. DynamicState::none()
+    };
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
+
+    let vs = vs::Shader::load(device.clone()).expect("failed to create vertex shader module");
+    let fs = fs::Shader::load(device.clone()).expect("failed to create fragment shader module");
+
+    let pipeline = Arc::new(GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(fs.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build_with_cache(pipeline_cache.cache())
+            .build(device.clone())
+            .unwrap()
+        );
+
+    let mut video = VideoWriter::create(device.clone(), &output, width, height, fps, Some(pipeline_cache.cache())).unwrap();
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
+    // One full turn of the triangle over the video, aspect corrected so it doesn't stretch.
+    for frame in 0 .. frames {
+        let push_constants = vs::ty::Rotation {
+            angle: 2.0 * PI * frame as f32 / frames as f32,
+            aspect: height as f32 / width as f32,
+        };
+
+        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family()).unwrap();
+        builder
+            .begin_render_pass(framebuffer.clone(), SubpassContents::Inline, vec![[0.0, 0.0, 1.0, 1.0].into()])
+            .unwrap()
+
+            .draw(pipeline.clone(), &dynamic_state, vertex_buffer.clone(), (), push_constants)
+            .unwrap()
+
+            .end_render_pass()
+            .unwrap();
+        video.record(&mut builder, image.clone());
+
+        let command_buffer = builder.build().unwrap();
+        let finished = command_buffer.execute(queue.clone()).unwrap();
+        finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+        video.write_frame().unwrap();
+    }
+
+    let path = video.path().to_path_buf();
+    video.finish().unwrap();
+    println!("Saved {} frames to {}", frames, path.display());
+
+}
+
+#[derive(Default, Copy, Clone)]
+struct Vertex {
+    position: [f32;2],
+}
+
+vulkano::impl_vertex!(Vertex, position);
@@ -0,0 +1,14 @@
+// The palette lookup table at binding 1 of set 0, as uploaded by `fractal::FractalRenderer` and
+// `deep_zoom::DeepZoomRenderer`.
+
+layout(set = 0, binding = 1) readonly buffer Palette {
+    vec4 colors[];
+} palette;
+
+// Color at `t` along the palette, which repeats every 1.0.
+vec3 palette_color(float t) {
+    uint len = uint(palette.colors.length());
+    float x = fract(t) * float(len - 1);
+    uint i = uint(x);
+    return mix(palette.colors[i].rgb, palette.colors[min(i + 1, len - 1)].rgb, x - float(i));
+}
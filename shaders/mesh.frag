@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec4 v_color;
+
+layout(location = 0) out vec4 f_color;
+
+// Two sided Lambert shading from a fixed directional light, in linear space. The render target
+// is sRGB, so the hardware encodes the result.
+const vec3 LIGHT = normalize(vec3(0.4, 0.8, 0.6));
+const float AMBIENT = 0.15;
+
+void main() {
+    vec3 n = normalize(v_normal);
+    float diffuse = abs(dot(n, LIGHT));
+    f_color = vec4(v_color.rgb * (AMBIENT + (1.0 - AMBIENT) * diffuse), v_color.a);
+}
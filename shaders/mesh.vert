@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec4 v_color;
+
+layout(push_constant) uniform Camera {
+    mat4 view_projection;
+} camera;
+
+void main() {
+    gl_Position = camera.view_projection * vec4(position, 1.0);
+    v_normal = normal;
+    v_color = color;
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec3 v_normal;
+layout(location = 1) in vec2 v_uv;
+layout(location = 2) in vec4 v_color;
+
+layout(location = 0) out vec4 f_color;
+
+// The material's base color texture, sRGB so sampling returns linear values.
+layout(set = 0, binding = 0) uniform sampler2D base_color_texture;
+
+const vec3 LIGHT = normalize(vec3(0.4, 0.8, 0.6));
+const float AMBIENT = 0.15;
+
+void main() {
+    vec4 base_color = v_color * texture(base_color_texture, v_uv);
+    float diffuse = abs(dot(normalize(v_normal), LIGHT));
+    f_color = vec4(base_color.rgb * (AMBIENT + (1.0 - AMBIENT) * diffuse), base_color.a);
+}
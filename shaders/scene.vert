@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec4 color;
+
+layout(location = 0) out vec3 v_normal;
+layout(location = 1) out vec2 v_uv;
+layout(location = 2) out vec4 v_color;
+
+// 128 bytes, the push constant size every device supports.
+layout(push_constant) uniform Transforms {
+    mat4 view_projection;
+    mat4 model;
+} transforms;
+
+void main() {
+    gl_Position = transforms.view_projection * transforms.model * vec4(position, 1.0);
+    // The inverse transpose keeps normals perpendicular under non-uniform scales.
+    v_normal = transpose(inverse(mat3(transforms.model))) * normal;
+    v_uv = uv;
+    v_color = color;
+    // Only read when drawing point lists.
+    gl_PointSize = 1.0;
+}
@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    // Only read when drawing point lists.
+    gl_PointSize = 4.0;
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+
+layout(push_constant) uniform Rotation {
+    float angle;
+    float aspect;
+} rotation;
+
+void main() {
+    float c = cos(rotation.angle);
+    float s = sin(rotation.angle);
+    vec2 p = vec2(c * position.x - s * position.y, s * position.x + c * position.y);
+    gl_Position = vec4(p.x * rotation.aspect, p.y, 0.0, 1.0);
+}
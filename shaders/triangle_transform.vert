@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+
+layout(push_constant) uniform Transform {
+    vec2 scale;
+    vec2 offset;
+} transform;
+
+void main() {
+    gl_Position = vec4(position * transform.scale + transform.offset, 0.0, 1.0);
+}
@@ -0,0 +1,398 @@
+use image::{Delay, Frame, ImageBuffer, Rgba};
+use image::gif::{GifEncoder, Repeat};
+
+use crate::cli::Args;
+use crate::fractal::View;
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "in" | "ease-in" => Some(Easing::EaseIn),
+            "out" | "ease-out" => Some(Easing::EaseOut),
+            "in-out" | "ease-in-out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.max(0.0).min(1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// A point of the path. The easing applies to the segment that starts at this keyframe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub center: [f64; 2],
+    pub zoom: f64,
+    pub easing: Easing,
+}
+
+impl Keyframe {
+    // Parses `x,y,zoom` with an optional `,easing` (linear by default). The zoom must be positive,
+    // `view_at` interpolates its logarithm.
+    pub fn parse(text: &str) -> Option<Keyframe> {
+        let parts: Vec<_> = text.split(',').map(|part| part.trim()).collect();
+        if parts.len() < 3 || parts.len() > 4 {
+            return None;
+        }
+
+        let keyframe = Keyframe {
+            center: [parts[0].parse().ok()?, parts[1].parse().ok()?],
+            zoom: parts[2].parse().ok()?,
+            easing: match parts.get(3) {
+                Some(name) => Easing::from_name(name)?,
+                None => Easing::Linear,
+            },
+        };
+
+        let finite = keyframe.center[0].is_finite() && keyframe.center[1].is_finite() && keyframe.zoom.is_finite();
+        if !finite || keyframe.zoom <= 0.0 {
+            return None;
+        }
+        Some(keyframe)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoomPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl ZoomPath {
+    // Keyframes separated by `;` or new lines, empty lines and lines starting with `//` are
+    // ignored.
+    pub fn parse(text: &str) -> Result<ZoomPath, String> {
+        let keyframes = text.split(|c| c == ';' || c == '\n')
+            .map(|keyframe| keyframe.trim())
+            .filter(|keyframe| !keyframe.is_empty() && !keyframe.starts_with("//"))
+            .map(|keyframe| Keyframe::parse(keyframe).ok_or_else(|| format!("invalid keyframe `{}`", keyframe)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if keyframes.len() < 2 {
+            return Err("a zoom path needs at least two keyframes".to_owned());
+        }
+        Ok(ZoomPath { keyframes })
+    }
+
+    // Reads `--keyframes`, either the keyframes themselves or a file containing them. Without it
+    // the path zooms from the default view of `view` into its center and zoom.
+    pub fn from_args(args: &Args, view: &View) -> ZoomPath {
+        match args.raw("--keyframes") {
+            Some(keyframes) => {
+                let text = if Path::new(keyframes).is_file() {
+                    fs::read_to_string(keyframes).unwrap_or_else(|e| panic!("failed to read {}: {}", keyframes, e))
+                } else {
+                    keyframes.to_owned()
+                };
+                ZoomPath::parse(&text).unwrap_or_else(|e| panic!("{}", e))
+            },
+            None => ZoomPath {
+                keyframes: vec![
+                    Keyframe { center: view.fractal.default_center(), zoom: 1.0, easing: Easing::EaseInOut },
+                    Keyframe { center: view.center, zoom: view.zoom(), easing: Easing::Linear },
+                ],
+            },
+        }
+    }
+
+    // The view at time `t` in [0, 1], every segment taking the same time. The zoom is
+    // interpolated geometrically so zooming in feels like a constant speed.
+    pub fn view_at(&self, base: &View, t: f64) -> View {
+        let segments = self.keyframes.len() - 1;
+        let position = t.max(0.0).min(1.0) * segments as f64;
+        let index = (position.floor() as usize).min(segments - 1);
+
+        let from = &self.keyframes[index];
+        let to = &self.keyframes[index + 1];
+        let s = from.easing.apply(position - index as f64);
+
+        let zoom = (from.zoom.ln() + (to.zoom.ln() - from.zoom.ln()) * s).exp();
+
+        View {
+            center: [
+                from.center[0] + (to.center[0] - from.center[0]) * s,
+                from.center[1] + (to.center[1] - from.center[1]) * s,
+            ],
+            scale: base.fractal.default_scale() / zoom,
+            .. *base
+        }
+    }
+
+    pub fn views(&self, base: &View, frames: u32) -> Vec<View> {
+        (0 .. frames)
+            .map(|frame| {
+                let t = if frames > 1 { frame as f64 / (frames - 1) as f64 } else { 0.0 };
+                self.view_at(base, t)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    // Numbered PNG files in a directory.
+    Frames,
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    // Reads `--format frames|gif|apng`, otherwise guesses from the output: `.gif` and `.png`
+    // files are animations, anything else is a directory of frames.
+    pub fn from_args(args: &Args, output: &Path) -> AnimationFormat {
+        match args.raw("--format") {
+            Some("frames") => AnimationFormat::Frames,
+            Some("gif") => AnimationFormat::Gif,
+            Some("apng") => AnimationFormat::Apng,
+            Some(other) => panic!("unknown animation format `{}`", other),
+            None => match output.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref() {
+                Some("gif") => AnimationFormat::Gif,
+                Some("png") | Some("apng") => AnimationFormat::Apng,
+                _ => AnimationFormat::Frames,
+            },
+        }
+    }
+}
+
+pub enum AnimationWriter {
+    Frames { directory: PathBuf, next: u32 },
+    Gif(GifEncoder<BufWriter<File>>, u32),
+    Apng(ApngWriter),
+}
+
+impl AnimationWriter {
+    pub fn create<P: AsRef<Path>>(format: AnimationFormat, path: P, width: u32, height: u32,
+                                  frames: u32, fps: u32) -> io::Result<AnimationWriter>
+    {
+        // GIF delays divide by the frame rate and APNG stores it as a u16.
+        if fps == 0 || fps > u16::MAX as u32 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("the frame rate must be between 1 and {}, got {}", u16::MAX, fps)));
+        }
+        if frames == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "an animation needs at least one frame"));
+        }
+
+        let path = path.as_ref();
+        Ok(match format {
+            AnimationFormat::Frames => {
+                fs::create_dir_all(path)?;
+                AnimationWriter::Frames { directory: path.to_owned(), next: 0 }
+            },
+            AnimationFormat::Gif => {
+                let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+                encoder.set_repeat(Repeat::Infinite).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                AnimationWriter::Gif(encoder, fps)
+            },
+            AnimationFormat::Apng => {
+                AnimationWriter::Apng(ApngWriter::create(path, width, height, frames, fps)?)
+            },
+        })
+    }
+
+    pub fn add_frame(&mut self, frame: ImageBuffer<Rgba<u8>, Vec<u8>>) -> io::Result<()> {
+        let other = |e: image::ImageError| io::Error::new(io::ErrorKind::Other, e);
+
+        match self {
+            AnimationWriter::Frames { directory, next } => {
+                frame.save(directory.join(format!("frame_{:05}.png", next))).map_err(other)?;
+                *next += 1;
+            },
+            AnimationWriter::Gif(encoder, fps) => {
+                let delay = Delay::from_numer_denom_ms(1000, *fps);
+                encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay)).map_err(other)?;
+            },
+            AnimationWriter::Apng(writer) => writer.add_frame(&frame)?,
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            AnimationWriter::Frames { .. } | AnimationWriter::Gif(..) => Ok(()),
+            AnimationWriter::Apng(writer) => writer.finish(),
+        }
+    }
+}
+
+// The png crate can't write animations, so every frame is encoded as a standalone PNG and its
+// IDAT chunks are copied into the animation as fdAT chunks.
+pub struct ApngWriter {
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+    fps: u32,
+    frames: u32,
+    written: u32,
+    sequence: u32,
+}
+
+impl ApngWriter {
+    pub fn create<P: AsRef<Path>>(path: P, width: u32, height: u32, frames: u32, fps: u32) -> io::Result<ApngWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        // 8 bits per channel, RGBA, deflate, adaptive filtering, no interlacing.
+        ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+        write_chunk(&mut file, b"IHDR", &ihdr)?;
+
+        let mut actl = Vec::with_capacity(8);
+        actl.extend_from_slice(&frames.to_be_bytes());
+        // Loop forever.
+        actl.extend_from_slice(&0u32.to_be_bytes());
+        write_chunk(&mut file, b"acTL", &actl)?;
+
+        Ok(ApngWriter { file, width, height, fps, frames, written: 0, sequence: 0 })
+    }
+
+    pub fn add_frame(&mut self, frame: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> io::Result<()> {
+        assert_eq!(frame.dimensions(), (self.width, self.height), "frame has the wrong size");
+        assert!(self.written < self.frames, "more frames than announced");
+
+        let mut fctl = Vec::with_capacity(26);
+        fctl.extend_from_slice(&self.sequence.to_be_bytes());
+        fctl.extend_from_slice(&self.width.to_be_bytes());
+        fctl.extend_from_slice(&self.height.to_be_bytes());
+        fctl.extend_from_slice(&0u32.to_be_bytes());
+        fctl.extend_from_slice(&0u32.to_be_bytes());
+        fctl.extend_from_slice(&1u16.to_be_bytes());
+        fctl.extend_from_slice(&(self.fps as u16).to_be_bytes());
+        // Dispose: none, blend: source.
+        fctl.extend_from_slice(&[0, 0]);
+        write_chunk(&mut self.file, b"fcTL", &fctl)?;
+        self.sequence += 1;
+
+        for data in encode_idat(frame)? {
+            if self.written == 0 {
+                write_chunk(&mut self.file, b"IDAT", &data)?;
+            } else {
+                let mut fdat = Vec::with_capacity(data.len() + 4);
+                fdat.extend_from_slice(&self.sequence.to_be_bytes());
+                fdat.extend_from_slice(&data);
+                write_chunk(&mut self.file, b"fdAT", &fdat)?;
+                self.sequence += 1;
+            }
+        }
+
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.written != self.frames {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("announced {} frames but wrote {}", self.frames, self.written)));
+        }
+        write_chunk(&mut self.file, b"IEND", &[])?;
+        self.file.flush()
+    }
+}
+
+// Returns the contents of the IDAT chunks of `frame` encoded as a PNG.
+fn encode_idat(frame: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> io::Result<Vec<Vec<u8>>> {
+    let mut png = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png, frame.width(), frame.height());
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        writer.write_image_data(frame).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = &png[8 ..];
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if &rest[4 .. 8] == b"IDAT" {
+            chunks.push(rest[8 .. 8 + length].to_vec());
+        }
+        rest = &rest[12 + length ..];
+    }
+    Ok(chunks)
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+
+    let crc = crc32(crc32(0, kind), data);
+    out.write_all(&crc.to_be_bytes())
+}
+
+// CRC-32 as used by PNG, continuing from `crc` so chunk type and data can be hashed in turn.
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0 .. 8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keyframes() {
+        let keyframe = Keyframe::parse("-0.75, 0.1, 1e3").unwrap();
+        assert_eq!(keyframe, Keyframe { center: [-0.75, 0.1], zoom: 1000.0, easing: Easing::Linear });
+
+        let keyframe = Keyframe::parse("0,0,2,ease-in-out").unwrap();
+        assert_eq!(keyframe.easing, Easing::EaseInOut);
+    }
+
+    #[test]
+    fn rejects_bad_keyframes() {
+        for text in ["", "0,0", "0,0,1,linear,1", "a,0,1", "0,0,1,bounce", "0,0,0", "0,0,-2", "0,0,NaN",
+                     "0,0,inf", "NaN,0,1", "0,inf,1"].iter() {
+            assert_eq!(Keyframe::parse(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn zoom_paths_need_two_keyframes() {
+        let path = ZoomPath::parse("// start\n0,0,1;\n-1,0,100,out\n").unwrap();
+        assert_eq!(path.keyframes.len(), 2);
+
+        assert!(ZoomPath::parse("0,0,1").is_err());
+        assert!(ZoomPath::parse("0,0,1;0,0,0").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_frame_rates_and_counts() {
+        let path = std::env::temp_dir().join("vulkano_guide_rejected.gif");
+        for &(frames, fps) in [(10, 0), (10, 65536), (0, 30)].iter() {
+            for &format in [AnimationFormat::Gif, AnimationFormat::Apng].iter() {
+                let error = AnimationWriter::create(format, &path, 4, 4, frames, fps).err().unwrap();
+                assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            }
+        }
+        assert!(!path.exists());
+    }
+}
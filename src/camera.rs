@@ -0,0 +1,92 @@
+// A perspective camera producing Vulkan clip space: y pointing down and depth in [0, 1].
+// Matrices are column major, as GLSL's `mat4` push constants expect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    // Vertical field of view, in radians.
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera {
+    // Looks at the box from `min` to `max` from the direction given by `yaw` and `pitch` (in
+    // degrees), far enough for all of it to fit in view.
+    pub fn framing(min: [f32; 3], max: [f32; 3], yaw: f32, pitch: f32) -> Camera {
+        let target = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, (min[2] + max[2]) / 2.0];
+        let radius = (length(sub(max, min)) / 2.0).max(1e-3);
+
+        let fov_y = 45f32.to_radians();
+        let distance = radius / (fov_y / 2.0).sin();
+
+        let (yaw, pitch) = (yaw.to_radians(), pitch.max(-89.0).min(89.0).to_radians());
+        let direction = [pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos()];
+        let eye = [target[0] + direction[0] * distance, target[1] + direction[1] * distance,
+                   target[2] + direction[2] * distance];
+
+        Camera { eye, target, up: [0.0, 1.0, 0.0], fov_y, near: (distance - radius).max(distance * 1e-3), far: distance + radius }
+    }
+
+    pub fn view(&self) -> [[f32; 4]; 4] {
+        let f = normalize(sub(self.target, self.eye));
+        let s = normalize(cross(f, self.up));
+        let u = cross(s, f);
+
+        [
+            [s[0], u[0], -f[0], 0.0],
+            [s[1], u[1], -f[1], 0.0],
+            [s[2], u[2], -f[2], 0.0],
+            [-dot(s, self.eye), -dot(u, self.eye), dot(f, self.eye), 1.0],
+        ]
+    }
+
+    pub fn projection(&self, aspect: f32) -> [[f32; 4]; 4] {
+        let f = 1.0 / (self.fov_y / 2.0).tan();
+        let range = self.near - self.far;
+
+        [
+            [f / aspect, 0.0, 0.0, 0.0],
+            [0.0, -f, 0.0, 0.0],
+            [0.0, 0.0, self.far / range, -1.0],
+            [0.0, 0.0, self.near * self.far / range, 0.0],
+        ]
+    }
+
+    // Projection times view, for an image `aspect` times wider than tall.
+    pub fn view_projection(&self, aspect: f32) -> [[f32; 4]; 4] {
+        multiply(&self.projection(aspect), &self.view())
+    }
+}
+
+pub fn multiply(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut m = [[0.0; 4]; 4];
+    for column in 0 .. 4 {
+        for row in 0 .. 4 {
+            m[column][row] = (0 .. 4).map(|k| a[k][row] * b[column][k]).sum();
+        }
+    }
+    m
+}
+
+pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+pub fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+pub fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let l = length(a);
+    if l > 0.0 { [a[0] / l, a[1] / l, a[2] / l] } else { a }
+}
@@ -0,0 +1,46 @@
+use std::env;
+use std::fmt::Debug;
+use std::str::FromStr;
+
+// Minimal `--name value` argument parsing shared by the examples.
+pub struct Args {
+    args: Vec<String>,
+}
+
+impl Args {
+    pub fn from_env() -> Args {
+        Args { args: env::args().skip(1).collect() }
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.args.iter().any(|arg| arg == name)
+    }
+
+    pub fn raw(&self, name: &str) -> Option<&str> {
+        self.args.iter()
+            .position(|arg| arg == name)
+            .map(|i| self.args.get(i + 1).unwrap_or_else(|| panic!("missing value for {}", name)).as_str())
+    }
+
+    pub fn value<T>(&self, name: &str) -> Option<T> where T: FromStr, T::Err: Debug {
+        self.raw(name).map(|value| {
+            value.parse().unwrap_or_else(|e| panic!("invalid value for {}: {:?}", name, e))
+        })
+    }
+
+    // Parses values such as `--center -0.75,0.1` or `--size 1920x1080`.
+    pub fn pair<T>(&self, name: &str, separator: char) -> Option<(T, T)> where T: FromStr, T::Err: Debug {
+        self.raw(name).map(|value| {
+            let mut parts = value.splitn(2, separator);
+            let mut next = || {
+                parts.next()
+                    .unwrap_or_else(|| panic!("expected two values separated by `{}` for {}", separator, name))
+                    .parse()
+                    .unwrap_or_else(|e| panic!("invalid value for {}: {:?}", name, e))
+            };
+            let first = next();
+            let second = next();
+            (first, second)
+        })
+    }
+}
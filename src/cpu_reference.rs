@@ -0,0 +1,173 @@
+use image::{ImageBuffer, Rgba};
+
+use num_traits::Float;
+
+use rayon::prelude::*;
+
+use crate::fractal::{Fractal, Precision, View};
+use crate::palette::{Coloring, LUT_SIZE};
+
+// Same as BAILOUT in the fractal shaders.
+const BAILOUT: f32 = 256.0;
+
+// A pixel differs when any channel is off by more than this.
+pub const TOLERANCE: u8 = 3;
+// Fraction of pixels allowed to differ, enough for the boundary pixels where fused
+// multiply-adds or the driver's log/pow change the escape iteration.
+pub const MAX_MISMATCH: f64 = 0.002;
+
+// Renders `view` on the CPU with the same escape-time algorithm, coloring and precision as
+// FractalRenderer, so the GPU output can be checked against it. Every step follows the shader
+// expression by expression, only fused multiply-adds and the precision of the transcendental
+// functions can make a pixel differ. Rows are rendered in parallel.
+pub fn render(view: &View, coloring: &Coloring, precision: Precision) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let lut = coloring.palette.lut(LUT_SIZE);
+    let period = coloring.period.unwrap_or(view.max_iterations.max(1) as f32);
+    let degree = match view.fractal {
+        Fractal::Multibrot { exponent } => exponent,
+        _ => 2.0,
+    };
+
+    let row_bytes = view.width as usize * 4;
+    let mut pixels = vec![0u8; row_bytes * view.height as usize];
+
+    pixels.par_chunks_mut(row_bytes).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.chunks_mut(4).enumerate() {
+            let escape = escape(view, x as u32, y as u32, precision);
+
+            let color = match escape {
+                Some((n, modulus)) => {
+                    let mut mu = n as f32;
+                    if coloring.smooth {
+                        mu += 1.0 - modulus.ln().ln() / degree.ln();
+                    }
+                    palette_color(&lut, coloring.offset + mu / period)
+                },
+                None => coloring.interior,
+            };
+
+            pixel[0] = to_unorm8(color[0]);
+            pixel[1] = to_unorm8(color[1]);
+            pixel[2] = to_unorm8(color[2]);
+            pixel[3] = 255;
+        }
+    });
+
+    ImageBuffer::from_raw(view.width, view.height, pixels).unwrap()
+}
+
+// Iterations until pixel (x, y) escapes and |z| at that point, or None for interior points.
+pub fn escape(view: &View, x: u32, y: u32, precision: Precision) -> Option<(u32, f32)> {
+    match precision {
+        Precision::Single => escape_time::<f32>(view, x, y, false),
+        Precision::Double => escape_time::<f64>(view, x, y, true),
+    }
+}
+
+// `exact_powers` mirrors the double precision shader, which multiplies out integer Multibrot
+// exponents.
+fn escape_time<T: Float>(view: &View, x: u32, y: u32, exact_powers: bool) -> Option<(u32, f32)> {
+    let cast = |value: f64| T::from(value).unwrap();
+    let half = cast(0.5);
+
+    let offset = [
+        T::from(x).unwrap() + half - T::from(view.width).unwrap() * half,
+        T::from(y).unwrap() + half - T::from(view.height).unwrap() * half,
+    ];
+    let pixel_size = cast(view.pixel_size());
+    let point = [
+        cast(view.center[0]) + offset[0] * pixel_size,
+        cast(view.center[1]) + -offset[1] * pixel_size,
+    ];
+
+    let (mut z, c) = match view.fractal {
+        Fractal::Julia { c } => (point, [cast(c[0]), cast(c[1])]),
+        _ => ([T::zero(), T::zero()], point),
+    };
+
+    let bailout = cast((BAILOUT * BAILOUT) as f64);
+
+    for n in 0 .. view.max_iterations {
+        z = iterate(z, c, &view.fractal, exact_powers);
+
+        if z[0] * z[0] + z[1] * z[1] > bailout {
+            let modulus = (z[0] * z[0] + z[1] * z[1]).sqrt();
+            return Some((n, modulus.to_f32().unwrap()));
+        }
+    }
+    None
+}
+
+fn iterate<T: Float>(z: [T; 2], c: [T; 2], fractal: &Fractal, exact_powers: bool) -> [T; 2] {
+    let two = T::from(2.0).unwrap();
+
+    match *fractal {
+        Fractal::BurningShip => {
+            let z = [z[0].abs(), z[1].abs()];
+            [z[0] * z[0] - z[1] * z[1] + c[0], two * z[0] * z[1] + c[1]]
+        },
+        Fractal::Tricorn => {
+            [z[0] * z[0] - z[1] * z[1] + c[0], -two * z[0] * z[1] + c[1]]
+        },
+        Fractal::Multibrot { exponent } => {
+            if exact_powers && exponent == exponent.floor() && exponent >= 1.0 {
+                let mut w = z;
+                for _ in 1 .. exponent as i32 {
+                    w = [w[0] * z[0] - w[1] * z[1], w[0] * z[1] + w[1] * z[0]];
+                }
+                return [w[0] + c[0], w[1] + c[1]];
+            }
+
+            let zf = [z[0].to_f32().unwrap(), z[1].to_f32().unwrap()];
+            let r = (zf[0] * zf[0] + zf[1] * zf[1]).sqrt().powf(exponent);
+            let theta = zf[1].atan2(zf[0]) * exponent;
+            [
+                T::from(r * theta.cos()).unwrap() + c[0],
+                T::from(r * theta.sin()).unwrap() + c[1],
+            ]
+        },
+        Fractal::Mandelbrot | Fractal::Julia { .. } => {
+            [z[0] * z[0] - z[1] * z[1] + c[0], two * z[0] * z[1] + c[1]]
+        },
+    }
+}
+
+// palette_color in the shaders.
+fn palette_color(lut: &[[f32; 4]], t: f32) -> [f32; 3] {
+    let len = lut.len();
+    let x = (t - t.floor()) * (len - 1) as f32;
+    let i = x as usize;
+    let a = lut[i];
+    let b = lut[(i + 1).min(len - 1)];
+    let f = x - i as f32;
+
+    [
+        a[0] * (1.0 - f) + b[0] * f,
+        a[1] * (1.0 - f) + b[1] * f,
+        a[2] * (1.0 - f) + b[2] * f,
+    ]
+}
+
+// Conversion of a float to an unorm8 image texel as done by imageStore.
+fn to_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Number of pixels where any channel differs by more than `TOLERANCE`, and an image with those
+// pixels in white.
+pub fn compare(a: &ImageBuffer<Rgba<u8>, Vec<u8>>, b: &ImageBuffer<Rgba<u8>, Vec<u8>>)
+    -> (usize, ImageBuffer<Rgba<u8>, Vec<u8>>)
+{
+    let mut diff = ImageBuffer::from_pixel(a.width(), a.height(), Rgba([0, 0, 0, 255]));
+    let mut mismatched = 0;
+
+    for ((x, y, pa), pb) in a.enumerate_pixels().zip(b.pixels()) {
+        let differs = pa.0.iter().zip(pb.0.iter()).any(|(&ca, &cb)| (ca as i16 - cb as i16).abs() > TOLERANCE as i16);
+        if differs {
+            diff.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            mismatched += 1;
+        }
+    }
+
+    (mismatched, diff)
+}
@@ -0,0 +1,455 @@
+use vulkano::device::{Device,Queue};
+use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder,CommandBuffer};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::{PipelineLayout,PipelineLayoutAbstract};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions,StorageImage};
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::sync::GpuFuture;
+
+use image::{ImageBuffer, Rgba};
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::cli::Args;
+use crate::palette::{Coloring, LUT_SIZE};
+use crate::readback::ImageDownload;
+use crate::shaders::deep_zoom_comp as cs;
+use crate::supersampling::{Supersampler, Supersampling};
+
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+
+// Fixed point number with `frac_bits` fractional bits, enough to hold the center of a view
+// far beyond what f64 can address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixed {
+    value: BigInt,
+    frac_bits: u32,
+}
+
+impl Fixed {
+    pub fn zero(frac_bits: u32) -> Fixed {
+        Fixed { value: BigInt::zero(), frac_bits }
+    }
+
+    // Parses decimals such as `-0.7436438870371587047521915`, `1.5e-3` or `2`.
+    pub fn parse(text: &str, frac_bits: u32) -> Option<Fixed> {
+        let text = text.trim();
+        let (mantissa, exponent) = match text.find(|c: char| c == 'e' || c == 'E') {
+            Some(i) => (&text[.. i], text[i + 1 ..].parse::<i64>().ok()?),
+            None => (text, 0),
+        };
+
+        let (negative, mantissa) = match mantissa.chars().next()? {
+            '-' => (true, &mantissa[1 ..]),
+            '+' => (false, &mantissa[1 ..]),
+            _ => (false, mantissa),
+        };
+
+        let (integer, fraction) = match mantissa.find('.') {
+            Some(i) => (&mantissa[.. i], &mantissa[i + 1 ..]),
+            None => (mantissa, ""),
+        };
+
+        let digits = format!("{}{}", integer, fraction);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut value = BigInt::parse_bytes(digits.as_bytes(), 10)? << frac_bits as usize;
+        let exponent = exponent - fraction.len() as i64;
+        if exponent >= 0 {
+            value *= num_traits::pow(BigInt::from(10), exponent as usize);
+        } else {
+            value /= num_traits::pow(BigInt::from(10), (-exponent) as usize);
+        }
+
+        if negative {
+            value = -value;
+        }
+
+        Some(Fixed { value, frac_bits })
+    }
+
+    // None for NaN and infinities.
+    pub fn from_f64(value: f64, frac_bits: u32) -> Option<Fixed> {
+        if !value.is_finite() {
+            return None;
+        }
+        Fixed::parse(&format!("{:e}", value), frac_bits)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Keep the 64 most significant bits, f64 can't hold more anyway.
+        let shift = self.value.bits().saturating_sub(64);
+        let mantissa = (&self.value >> shift as usize).to_f64().unwrap_or(0.0);
+
+        // Scale in steps, a single power of two could overflow or underflow on its own.
+        let mut result = mantissa;
+        let mut exponent = shift as i64 - self.frac_bits as i64;
+        while exponent < -1000 {
+            result *= 2f64.powi(-1000);
+            exponent += 1000;
+        }
+        while exponent > 1000 {
+            result *= 2f64.powi(1000);
+            exponent -= 1000;
+        }
+        result * 2f64.powi(exponent as i32)
+    }
+
+    fn add(&self, other: &Fixed) -> Fixed {
+        Fixed { value: &self.value + &other.value, frac_bits: self.frac_bits }
+    }
+
+    fn sub(&self, other: &Fixed) -> Fixed {
+        Fixed { value: &self.value - &other.value, frac_bits: self.frac_bits }
+    }
+
+    fn mul(&self, other: &Fixed) -> Fixed {
+        Fixed { value: (&self.value * &other.value) >> self.frac_bits as usize, frac_bits: self.frac_bits }
+    }
+}
+
+// Like `fractal::View`, but the center is kept as text until the precision needed by the
+// zoom level is known. Only the Mandelbrot set is supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeepView {
+    pub center: [String; 2],
+    pub scale: f64,
+    pub width: u32,
+    pub height: u32,
+    pub max_iterations: u32,
+}
+
+impl Default for DeepView {
+    fn default() -> DeepView {
+        DeepView {
+            center: ["-1.0".to_owned(), "0.0".to_owned()],
+            scale: 2.0,
+            width: 1024,
+            height: 1024,
+            max_iterations: 1000,
+        }
+    }
+}
+
+impl DeepView {
+    // Same options as `fractal::View::from_args`, but `--center` keeps all its digits.
+    pub fn from_args(args: &Args) -> DeepView {
+        let mut view = DeepView::default();
+
+        if let Some(center) = args.raw("--center") {
+            let mut parts = center.splitn(2, ',');
+            let mut next = || parts.next().expect("expected --center re,im").trim().to_owned();
+            view.center = [next(), next()];
+        }
+        if let Some(zoom) = args.value::<f64>("--zoom") {
+            if !(zoom > 0.0 && zoom.is_finite()) {
+                panic!("--zoom must be a positive number, got {}", zoom);
+            }
+            view.scale = 2.0 / zoom;
+        }
+        if let Some(scale) = args.value::<f64>("--scale") {
+            if !(scale > 0.0 && scale.is_finite()) {
+                panic!("--scale must be a positive number, got {}", scale);
+            }
+            view.scale = scale;
+        }
+        if let Some((width, height)) = args.pair("--size", 'x') {
+            view.width = width;
+            view.height = height;
+        }
+        if let Some(max_iterations) = args.value("--iterations") {
+            view.max_iterations = max_iterations;
+        }
+
+        view
+    }
+
+    pub fn pixel_size(&self) -> f64 {
+        self.scale / self.height as f64
+    }
+
+    // Fractional bits needed to tell neighbouring pixels apart, plus a margin for the error that
+    // accumulates along the reference orbit.
+    pub fn frac_bits(&self) -> u32 {
+        (-self.pixel_size().log2()).max(0.0).ceil() as u32 + 64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReferenceOrbit {
+    // Z_0 = 0, Z_1, ... rounded to f64, up to the maximum iteration count or until it escapes.
+    pub points: Vec<[f64; 2]>,
+    pub escaped: bool,
+}
+
+const BAILOUT: f64 = 256.0;
+
+pub fn reference_orbit(center: &[Fixed; 2], max_iterations: u32) -> ReferenceOrbit {
+    let frac_bits = center[0].frac_bits;
+    let mut z = [Fixed::zero(frac_bits), Fixed::zero(frac_bits)];
+    let mut points = vec![[0.0, 0.0]];
+    let mut escaped = false;
+
+    for _ in 0 .. max_iterations {
+        let re2 = z[0].mul(&z[0]);
+        let im2 = z[1].mul(&z[1]);
+        let re_im = z[0].mul(&z[1]);
+
+        z = [re2.sub(&im2).add(&center[0]), re_im.add(&re_im).add(&center[1])];
+
+        let point = [z[0].to_f64(), z[1].to_f64()];
+        points.push(point);
+
+        if point[0] * point[0] + point[1] * point[1] > BAILOUT * BAILOUT {
+            escaped = true;
+            break;
+        }
+    }
+
+    ReferenceOrbit { points, escaped }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeepZoomError {
+    // The device doesn't support `shader_float64`, or it wasn't enabled.
+    NoFloat64,
+    // The pixel size is below `MIN_PIXEL_SIZE`.
+    TooDeep(f64),
+}
+
+impl fmt::Display for DeepZoomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DeepZoomError::NoFloat64 =>
+                write!(f, "deep zoom needs the shader_float64 feature, which this device doesn't support or wasn't enabled"),
+            DeepZoomError::TooDeep(pixel_size) =>
+                write!(f, "pixels of size {:e} are too small for f64 deltas, the limit is {:e}", pixel_size, MIN_PIXEL_SIZE),
+        }
+    }
+}
+
+impl error::Error for DeepZoomError {}
+
+// Smallest pixel size the f64 deltas can hold, with some room above the smallest normal f64
+// for the view's width in pixels and the squares in the iteration.
+pub const MIN_PIXEL_SIZE: f64 = 1e-290;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeepZoomReport {
+    pub frac_bits: u32,
+    pub reference_length: usize,
+    pub reference_escaped: bool,
+    pub glitched_pixels: u32,
+}
+
+// Perturbation renderer: the reference orbit at the center is computed on the CPU with
+// arbitrary precision and every pixel only iterates its f64 difference from it, rebasing onto
+// the start of the orbit when that difference stops being small (glitches) or the orbit ends.
+// Deltas are f64, so zooms are limited by the f64 exponent range (`MIN_PIXEL_SIZE`), not by
+// its precision.
+pub struct DeepZoomRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pipeline: Arc<ComputePipeline<PipelineLayout<cs::Layout>>>,
+    local_size: [u32; 3],
+    coloring: Coloring,
+    palette_buffer: Arc<CpuAccessibleBuffer<[[f32; 4]]>>,
+    supersampler: Supersampler,
+    supersampling: Supersampling,
+}
+
+impl DeepZoomRenderer {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, local_size: [u32; 3], cache: Option<Arc<PipelineCache>>)
+        -> Result<DeepZoomRenderer, DeepZoomError>
+    {
+        // f32 deltas would only reach a zoom of about 1e30, which plain double precision
+        // rendering already gets close to.
+        if !device.enabled_features().shader_float64 {
+            return Err(DeepZoomError::NoFloat64);
+        }
+
+        let shader = cs::Shader::load(device.clone()).expect("failed to create shader module");
+        let spec_consts = cs::SpecializationConstants {
+            constant_0: local_size[0],
+            constant_1: local_size[1],
+            .. Default::default()
+        };
+
+        let pipeline = Arc::new(
+            ComputePipeline::new(device.clone(), &shader.main_entry_point(), &spec_consts, cache.clone())
+                    .expect("failed to create compute pipeline"));
+
+        let coloring = Coloring::default();
+        let palette_buffer = upload_palette(&device, &coloring);
+        let supersampler = Supersampler::new(device.clone(), queue.clone(), cache);
+
+        Ok(DeepZoomRenderer {
+            device, queue, pipeline, local_size, coloring, palette_buffer, supersampler,
+            supersampling: Supersampling::Off,
+        })
+    }
+
+    pub fn set_coloring(&mut self, coloring: Coloring) {
+        if coloring.palette != self.coloring.palette {
+            self.palette_buffer = upload_palette(&self.device, &coloring);
+        }
+        self.coloring = coloring;
+    }
+
+    pub fn set_supersampling(&mut self, supersampling: Supersampling) {
+        self.supersampling = supersampling;
+    }
+
+    // With supersampling `glitched_pixels` counts glitched samples.
+    pub fn render(&self, view: &DeepView) -> Result<(ImageBuffer<Rgba<u8>, Vec<u8>>, DeepZoomReport), DeepZoomError> {
+        if view.pixel_size() < MIN_PIXEL_SIZE {
+            return Err(DeepZoomError::TooDeep(view.pixel_size()));
+        }
+
+        let frac_bits = view.frac_bits();
+        let parse = |text: &str| Fixed::parse(text, frac_bits)
+            .unwrap_or_else(|| panic!("invalid center coordinate `{}`", text));
+        let center = [parse(&view.center[0]), parse(&view.center[1])];
+
+        let orbit = reference_orbit(&center, view.max_iterations);
+
+        let reference_buffer = CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::all(), false,
+                                                              orbit.points.iter().cloned())
+                                .expect("failed to create buffer");
+        let glitch_buffer = CpuAccessibleBuffer::from_data(self.device.clone(), BufferUsage::all(), false, 0u32)
+                                .expect("failed to create buffer");
+
+        let image = StorageImage::new(self.device.clone(), Dimensions::Dim2d { width: view.width, height: view.height },
+                                      Format::R8G8B8A8Unorm, Some(self.queue.family())).unwrap();
+
+        let push_constants = cs::ty::Params {
+            interior_color: [self.coloring.interior[0], self.coloring.interior[1], self.coloring.interior[2], 1.0],
+            sample_offset: [0.5, 0.5],
+            pixel_size: view.pixel_size(),
+            max_iterations: view.max_iterations,
+            reference_length: orbit.points.len() as u32,
+            palette_offset: self.coloring.offset,
+            palette_period: self.coloring.period.unwrap_or(view.max_iterations.max(1) as f32),
+            smooth_coloring: self.coloring.smooth as u32,
+            glitch_tolerance: 1e-6,
+        };
+
+        let groups = [
+            (view.width + self.local_size[0] - 1) / self.local_size[0],
+            (view.height + self.local_size[1] - 1) / self.local_size[1],
+            1,
+        ];
+
+        let mut builder = AutoCommandBufferBuilder::new(self.device.clone(), self.queue.family()).unwrap();
+        self.supersampler.record(&mut builder, image.clone(), self.supersampling, |builder, image, sample_offset| {
+            let layout = self.pipeline.layout().descriptor_set_layout(0).unwrap();
+            let set = Arc::new(
+                PersistentDescriptorSet::start(layout.clone())
+                .add_image(image).unwrap()
+                .add_buffer(self.palette_buffer.clone()).unwrap()
+                .add_buffer(reference_buffer.clone()).unwrap()
+                .add_buffer(glitch_buffer.clone()).unwrap()
+                .build().unwrap(),
+            );
+
+            builder
+                .dispatch(groups, self.pipeline.clone(), set, cs::ty::Params { sample_offset, .. push_constants }).unwrap();
+        });
+        let download = ImageDownload::record(&mut builder, self.device.clone(), image, 0, 0).unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let finished = command_buffer.execute(self.queue.clone()).unwrap();
+        finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+        let report = DeepZoomReport {
+            frac_bits,
+            reference_length: orbit.points.len(),
+            reference_escaped: orbit.escaped,
+            glitched_pixels: *glitch_buffer.read().unwrap(),
+        };
+
+        Ok((download.read().unwrap().into_rgba8().unwrap(), report))
+    }
+}
+
+fn upload_palette(device: &Arc<Device>, coloring: &Coloring) -> Arc<CpuAccessibleBuffer<[[f32; 4]]>> {
+    CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, coloring.palette.lut(LUT_SIZE).into_iter())
+        .expect("failed to create buffer")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(value: i64, frac_bits: u32) -> Fixed {
+        Fixed { value: BigInt::from(value), frac_bits }
+    }
+
+    #[test]
+    fn parses_decimals() {
+        assert_eq!(Fixed::parse("2", 4), Some(fixed(32, 4)));
+        assert_eq!(Fixed::parse("1.5", 4), Some(fixed(24, 4)));
+        assert_eq!(Fixed::parse("-0.25", 8), Some(fixed(-64, 8)));
+        assert_eq!(Fixed::parse("+0.75", 8), Some(fixed(192, 8)));
+        assert_eq!(Fixed::parse("25e-2", 8), Some(fixed(64, 8)));
+        assert_eq!(Fixed::parse("0.03125E2", 8), Some(fixed(800, 8)));
+        // 1.5e-3 * 2^20 = 1572.864, truncated.
+        assert_eq!(Fixed::parse("1.5e-3", 20), Some(fixed(1572, 20)));
+    }
+
+    #[test]
+    fn rejects_bad_decimals() {
+        for text in ["", "-", ".", "e3", "1e", "1.2.3", "0x10", "1,5", "½"].iter() {
+            assert_eq!(Fixed::parse(text, 8), None, "{:?}", text);
+        }
+        assert_eq!(Fixed::from_f64(f64::NAN, 8), None);
+        assert_eq!(Fixed::from_f64(f64::INFINITY, 8), None);
+        assert_eq!(Fixed::from_f64(-0.375, 8), Some(fixed(-96, 8)));
+    }
+
+    #[test]
+    fn multiplies() {
+        // 1.5 * 2.5 = 3.75 and -0.5 * 0.5 = -0.25.
+        assert_eq!(fixed(24, 4).mul(&fixed(40, 4)), fixed(60, 4));
+        assert_eq!(fixed(-128, 8).mul(&fixed(128, 8)), fixed(-64, 8));
+        // 2^-8 * 2^-8 is below the last fractional bit.
+        assert_eq!(fixed(1, 8).mul(&fixed(1, 8)), fixed(0, 8));
+    }
+
+    #[test]
+    fn converts_to_f64() {
+        assert_eq!(fixed(24, 4).to_f64(), 1.5);
+        assert_eq!(fixed(-64, 8).to_f64(), -0.25);
+        assert_eq!(fixed(0, 8).to_f64(), 0.0);
+        // The smallest subnormal f64 and a value far beyond 64 bits of precision.
+        assert_eq!(fixed(1, 1074).to_f64(), f64::from_bits(1));
+        let big = Fixed { value: BigInt::from(3) << 2000usize, frac_bits: 1000 };
+        assert_eq!(big.to_f64(), 3.0 * 2f64.powi(1000));
+        let precise = Fixed::parse("-0.7436438870371587047521915", 512).unwrap();
+        assert_eq!(precise.to_f64(), -0.7436438870371587);
+    }
+
+    #[test]
+    fn reference_orbits() {
+        // -1 cycles between 0 and -1, 1 escapes after 0, 1, 2, 5, 26, 677.
+        let center = [Fixed::parse("-1", 64).unwrap(), Fixed::zero(64)];
+        let orbit = reference_orbit(&center, 4);
+        assert_eq!(orbit.points, vec![[0.0, 0.0], [-1.0, 0.0], [0.0, 0.0], [-1.0, 0.0], [0.0, 0.0]]);
+        assert!(!orbit.escaped);
+
+        let center = [Fixed::parse("1", 64).unwrap(), Fixed::zero(64)];
+        let orbit = reference_orbit(&center, 100);
+        let re: Vec<f64> = orbit.points.iter().map(|p| p[0]).collect();
+        assert_eq!(re, vec![0.0, 1.0, 2.0, 5.0, 26.0, 677.0]);
+        assert!(orbit.escaped);
+    }
+}
@@ -0,0 +1,65 @@
+use exr::prelude::{AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, WritableImage};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Unquantized output of an escape-time render, row by row from the top left pixel. Interior
+// points have `max_iterations` iterations and |z| is taken right after the last iteration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EscapeData {
+    pub width: u32,
+    pub height: u32,
+    pub max_iterations: u32,
+    pub iterations: Vec<f32>,
+    pub modulus: Vec<f32>,
+}
+
+impl EscapeData {
+    // Writes `<stem>_iterations.npy` and `<stem>_modulus.npy` next to `path`, as little endian
+    // f32 arrays of shape (height, width), and returns their paths.
+    pub fn save_npy<P: AsRef<Path>>(&self, path: P) -> io::Result<[PathBuf; 2]> {
+        let path = path.as_ref();
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("escape");
+        let iterations = path.with_file_name(format!("{}_iterations.npy", stem));
+        let modulus = path.with_file_name(format!("{}_modulus.npy", stem));
+
+        write_npy(&iterations, self.width, self.height, &self.iterations)?;
+        write_npy(&modulus, self.width, self.height, &self.modulus)?;
+        Ok([iterations, modulus])
+    }
+
+    // Writes an OpenEXR file with two 32-bit float channels, `iterations` and `modulus`.
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let channels = AnyChannels::sort(vec![
+            AnyChannel::new("iterations", FlatSamples::F32(self.iterations.clone())),
+            AnyChannel::new("modulus", FlatSamples::F32(self.modulus.clone())),
+        ].into());
+
+        let layer = Layer::new((self.width as usize, self.height as usize), LayerAttributes::named("escape time"),
+                               Encoding::FAST_LOSSLESS, channels);
+
+        Image::from_layer(layer).write().to_file(path)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+    }
+}
+
+// NPY format version 1.0: magic, version, header length and a Python dict literal padded with
+// spaces so the data starts at a multiple of 64 bytes.
+fn write_npy(path: &Path, width: u32, height: u32, data: &[f32]) -> io::Result<()> {
+    assert_eq!(data.len(), width as usize * height as usize, "data doesn't match the shape");
+
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}", height, width);
+    let unpadded = 10 + header.len() + 1;
+    header.extend(std::iter::repeat(' ').take((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x93NUMPY\x01\x00")?;
+    file.write_all(&(header.len() as u16).to_le_bytes())?;
+    file.write_all(header.as_bytes())?;
+    for value in data {
+        file.write_all(&value.to_le_bytes())?;
+    }
+    file.flush()
+}
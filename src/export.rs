@@ -0,0 +1,380 @@
+use vulkano::format::Format;
+
+use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb, Rgba};
+use image::hdr::HdrEncoder;
+use image::jpeg::JpegEncoder;
+
+use crate::cli::Args;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg { quality: u8 },
+    Exr,
+    Hdr,
+    Tiff,
+    Ppm,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.to_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg { quality: 90 }),
+            "exr" => Some(OutputFormat::Exr),
+            "hdr" => Some(OutputFormat::Hdr),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            "ppm" => Some(OutputFormat::Ppm),
+            _ => None,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<OutputFormat> {
+        path.as_ref().extension().and_then(|ext| ext.to_str()).and_then(OutputFormat::from_name)
+    }
+
+    // Reads `--image-format png|jpeg|exr|hdr|tiff|ppm` and `--quality q` for JPEG, otherwise
+    // picks the format from the extension of `path`, PNG when it has none.
+    pub fn from_args<P: AsRef<Path>>(args: &Args, path: P) -> OutputFormat {
+        let format = match args.raw("--image-format") {
+            Some(name) => OutputFormat::from_name(name).unwrap_or_else(|| panic!("unknown image format `{}`", name)),
+            None => OutputFormat::from_path(path).unwrap_or(OutputFormat::Png),
+        };
+
+        match (format, args.value("--quality")) {
+            (OutputFormat::Jpeg { .. }, Some(quality)) => OutputFormat::Jpeg { quality },
+            (format, _) => format,
+        }
+    }
+
+    pub fn supports_alpha(&self) -> bool {
+        match *self {
+            OutputFormat::Png | OutputFormat::Exr | OutputFormat::Tiff => true,
+            OutputFormat::Jpeg { .. } | OutputFormat::Hdr | OutputFormat::Ppm => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Samples {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    F32(Vec<f32>),
+}
+
+// Pixels read back from a render target. Integer samples are display encoded (sRGB), as every
+// shader in the project writes them, and float samples are linear; saving converts between the
+// two as the output format needs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportImage {
+    pub width: u32,
+    pub height: u32,
+    // 1 (gray), 2 (red and green), 3 (RGB) or 4 (RGBA), alpha is only ever the 4th channel.
+    pub channels: usize,
+    pub samples: Samples,
+}
+
+impl ExportImage {
+    pub fn from_rgba8(width: u32, height: u32, pixels: Vec<u8>) -> ExportImage {
+        ExportImage { width, height, channels: 4, samples: Samples::U8(pixels) }
+    }
+
+    // Interprets tightly packed texels of `format` as copied to a buffer by
+    // `copy_image_to_buffer`. Returns None for formats that aren't color targets or when `bytes`
+    // doesn't hold exactly `width * height` texels.
+    pub fn from_bytes(width: u32, height: u32, format: Format, bytes: &[u8]) -> Option<ExportImage> {
+        let u16s = || bytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect::<Vec<_>>();
+        let halfs = || bytes.chunks_exact(2).map(|b| f16_to_f32(u16::from_le_bytes([b[0], b[1]]))).collect::<Vec<_>>();
+        let f32s = || bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect::<Vec<_>>();
+
+        let (channels, samples) = match format {
+            Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => (4, Samples::U8(bytes.to_vec())),
+            Format::R8G8B8Unorm | Format::R8G8B8Srgb => (3, Samples::U8(bytes.to_vec())),
+            Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => {
+                let mut rgba = bytes.to_vec();
+                rgba.chunks_exact_mut(4).for_each(|texel| texel.swap(0, 2));
+                (4, Samples::U8(rgba))
+            },
+            Format::R16G16B16A16Unorm => (4, Samples::U16(u16s())),
+            Format::R16G16B16Unorm => (3, Samples::U16(u16s())),
+            Format::R16G16B16A16Sfloat => (4, Samples::F32(halfs())),
+            Format::R16G16B16Sfloat => (3, Samples::F32(halfs())),
+            Format::R32G32B32A32Sfloat => (4, Samples::F32(f32s())),
+            Format::R32G32B32Sfloat => (3, Samples::F32(f32s())),
+            Format::R8G8Unorm | Format::R8G8Srgb => (2, Samples::U8(bytes.to_vec())),
+            Format::R8Unorm | Format::R8Srgb => (1, Samples::U8(bytes.to_vec())),
+            Format::R16G16Unorm => (2, Samples::U16(u16s())),
+            Format::R16Unorm => (1, Samples::U16(u16s())),
+            Format::R16G16Sfloat => (2, Samples::F32(halfs())),
+            Format::R16Sfloat => (1, Samples::F32(halfs())),
+            Format::R32G32Sfloat => (2, Samples::F32(f32s())),
+            Format::R32Sfloat => (1, Samples::F32(f32s())),
+            _ => return None,
+        };
+
+        let image = ExportImage { width, height, channels, samples };
+        if image.len() != width as usize * height as usize * channels {
+            return None;
+        }
+        Some(image)
+    }
+
+    fn len(&self) -> usize {
+        match self.samples {
+            Samples::U8(ref s) => s.len(),
+            Samples::U16(ref s) => s.len(),
+            Samples::F32(ref s) => s.len(),
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.channels == 4
+    }
+
+    // The pixels of 8-bit RGBA images, like the ones the fractal kernels render.
+    pub fn into_rgba8(self) -> Option<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+        match self.samples {
+            Samples::U8(samples) if self.channels == 4 => ImageBuffer::from_raw(self.width, self.height, samples),
+            _ => None,
+        }
+    }
+
+    // The samples of float images as they are, e.g. the escape data of `R32Sfloat` images.
+    pub fn into_f32(self) -> Option<Vec<f32>> {
+        match self.samples {
+            Samples::F32(samples) => Some(samples),
+            _ => None,
+        }
+    }
+
+    // Gray images become RGB with three equal channels and red-green ones get a zero blue channel,
+    // RGB and RGBA images stay as they are.
+    pub fn to_rgb(&self) -> ExportImage {
+        fn expand<T: Copy + Default>(samples: &[T], channels: usize) -> Vec<T> {
+            samples.chunks(channels)
+                .flat_map(|texel| match *texel {
+                    [gray] => [gray, gray, gray],
+                    [red, green] => [red, green, T::default()],
+                    _ => unreachable!(),
+                }.to_vec())
+                .collect()
+        }
+
+        if self.channels >= 3 {
+            return self.clone();
+        }
+
+        let samples = match self.samples {
+            Samples::U8(ref s) => Samples::U8(expand(s, self.channels)),
+            Samples::U16(ref s) => Samples::U16(expand(s, self.channels)),
+            Samples::F32(ref s) => Samples::F32(expand(s, self.channels)),
+        };
+        ExportImage { channels: 3, samples, .. *self }
+    }
+
+    pub fn without_alpha(&self) -> ExportImage {
+        if !self.has_alpha() {
+            return self.clone();
+        }
+
+        fn rgb<T: Copy>(samples: &[T]) -> Vec<T> {
+            samples.chunks(4).flat_map(|texel| texel[.. 3].iter().cloned()).collect()
+        }
+
+        let samples = match self.samples {
+            Samples::U8(ref s) => Samples::U8(rgb(s)),
+            Samples::U16(ref s) => Samples::U16(rgb(s)),
+            Samples::F32(ref s) => Samples::F32(rgb(s)),
+        };
+        ExportImage { channels: 3, samples, .. *self }
+    }
+
+    // Display encoded 8 or 16-bit image, keeping the bit depth of integer samples and using
+    // 16 bits for floats. One and two channel images become RGB.
+    pub fn to_dynamic_image(&self) -> DynamicImage {
+        if self.channels < 3 {
+            return self.to_rgb().to_dynamic_image();
+        }
+
+        let (w, h) = (self.width, self.height);
+        let alpha = self.has_alpha();
+
+        match self.samples {
+            Samples::U8(ref s) if alpha => DynamicImage::ImageRgba8(ImageBuffer::from_raw(w, h, s.clone()).unwrap()),
+            Samples::U8(ref s) => DynamicImage::ImageRgb8(ImageBuffer::from_raw(w, h, s.clone()).unwrap()),
+            Samples::U16(ref s) if alpha => DynamicImage::ImageRgba16(ImageBuffer::from_raw(w, h, s.clone()).unwrap()),
+            Samples::U16(ref s) => DynamicImage::ImageRgb16(ImageBuffer::from_raw(w, h, s.clone()).unwrap()),
+            Samples::F32(ref s) => {
+                let encoded = s.iter().enumerate()
+                    .map(|(i, &value)| {
+                        let value = if alpha && i % 4 == 3 { value } else { linear_to_srgb(value) };
+                        (value.max(0.0).min(1.0) * 65535.0).round() as u16
+                    })
+                    .collect();
+                if alpha {
+                    DynamicImage::ImageRgba16(ImageBuffer::from_raw(w, h, encoded).unwrap())
+                } else {
+                    DynamicImage::ImageRgb16(ImageBuffer::from_raw(w, h, encoded).unwrap())
+                }
+            },
+        }
+    }
+
+    // Linear float samples, with the same channels.
+    pub fn to_linear(&self) -> Vec<f32> {
+        let decode = |i: usize, value: f32| {
+            if self.has_alpha() && i % 4 == 3 { value } else { srgb_to_linear(value) }
+        };
+
+        match self.samples {
+            Samples::U8(ref s) => s.iter().enumerate().map(|(i, &v)| decode(i, v as f32 / 255.0)).collect(),
+            Samples::U16(ref s) => s.iter().enumerate().map(|(i, &v)| decode(i, v as f32 / 65535.0)).collect(),
+            Samples::F32(ref s) => s.clone(),
+        }
+    }
+
+    // Formats without alpha drop it, JPEG is always 8 bits.
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: OutputFormat) -> io::Result<()> {
+        let path = path.as_ref();
+        let image = if format.supports_alpha() { self.to_rgb() } else { self.to_rgb().without_alpha() };
+        let other = |e: image::ImageError| io::Error::new(io::ErrorKind::Other, e);
+
+        match format {
+            OutputFormat::Png => image.to_dynamic_image().save_with_format(path, ImageFormat::Png).map_err(other),
+            OutputFormat::Tiff => image.to_dynamic_image().save_with_format(path, ImageFormat::Tiff).map_err(other),
+            OutputFormat::Jpeg { quality } => {
+                let rgb = image.to_dynamic_image().to_rgb8();
+                let mut file = BufWriter::new(File::create(path)?);
+                JpegEncoder::new_with_quality(&mut file, quality).encode_image(&rgb).map_err(other)?;
+                file.flush()
+            },
+            OutputFormat::Hdr => {
+                let linear = image.to_linear();
+                let pixels: Vec<_> = linear.chunks(3).map(|texel| Rgb([texel[0], texel[1], texel[2]])).collect();
+                let file = BufWriter::new(File::create(path)?);
+                HdrEncoder::new(file).encode(&pixels, image.width as usize, image.height as usize).map_err(other)
+            },
+            OutputFormat::Exr => {
+                let linear = image.to_linear();
+                let channels = image.channels;
+                let width = image.width as usize;
+                let texel = |x: usize, y: usize| &linear[(y * width + x) * channels ..];
+
+                let result = if image.has_alpha() {
+                    exr::prelude::write_rgba_file(path, width, image.height as usize, |x, y| {
+                        let t = texel(x, y);
+                        (t[0], t[1], t[2], t[3])
+                    })
+                } else {
+                    exr::prelude::write_rgb_file(path, width, image.height as usize, |x, y| {
+                        let t = texel(x, y);
+                        (t[0], t[1], t[2])
+                    })
+                };
+                result.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            },
+            OutputFormat::Ppm => write_ppm(path, &image),
+        }
+    }
+}
+
+// Binary PPM, with 16-bit samples for anything deeper than 8 bits.
+fn write_ppm(path: &Path, image: &ExportImage) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    match image.to_dynamic_image() {
+        DynamicImage::ImageRgb8(rgb) => {
+            write!(file, "P6\n{} {}\n255\n", image.width, image.height)?;
+            file.write_all(&rgb)?;
+        },
+        DynamicImage::ImageRgb16(rgb) => {
+            write!(file, "P6\n{} {}\n65535\n", image.width, image.height)?;
+            for &sample in rgb.iter() {
+                file.write_all(&sample.to_be_bytes())?;
+            }
+        },
+        _ => unreachable!("PPM images have no alpha"),
+    }
+    file.flush()
+}
+
+// Picks the format from `args` and `path` and saves.
+pub fn save<P: AsRef<Path>>(image: &ExportImage, path: P, args: &Args) -> io::Result<()> {
+    let format = OutputFormat::from_args(args, path.as_ref());
+    image.save(path, format)
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_color_formats() {
+        let image = ExportImage::from_bytes(2, 1, Format::B8G8R8A8Unorm, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert_eq!((image.channels, image.samples), (4, Samples::U8(vec![3, 2, 1, 4, 7, 6, 5, 8])));
+
+        let half_one = 0x3c00u16.to_le_bytes();
+        let image = ExportImage::from_bytes(1, 1, Format::R16G16B16Sfloat, &[half_one, half_one, [0, 0]].concat()).unwrap();
+        assert_eq!(image.samples, Samples::F32(vec![1.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn reads_one_and_two_channel_formats() {
+        let bytes: Vec<u8> = [1.5f32, -2.0].iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+        let image = ExportImage::from_bytes(2, 1, Format::R32Sfloat, &bytes).unwrap();
+        assert_eq!(image.channels, 1);
+        assert_eq!(image.into_f32(), Some(vec![1.5, -2.0]));
+
+        let image = ExportImage::from_bytes(1, 1, Format::R32G32Sfloat, &bytes).unwrap();
+        assert_eq!(image.channels, 2);
+
+        let image = ExportImage::from_bytes(2, 1, Format::R8G8Unorm, &[10, 20, 30, 40]).unwrap();
+        assert_eq!(image.to_rgb().samples, Samples::U8(vec![10, 20, 0, 30, 40, 0]));
+
+        let image = ExportImage::from_bytes(2, 1, Format::R16Unorm, &[1, 0, 2, 0]).unwrap();
+        assert_eq!(image.to_rgb().samples, Samples::U16(vec![1, 1, 1, 2, 2, 2]));
+        assert_eq!(image.to_dynamic_image().to_rgb16().into_raw(), vec![1, 1, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn rejects_mismatched_sizes_and_unknown_formats() {
+        assert_eq!(ExportImage::from_bytes(2, 2, Format::R8G8B8A8Unorm, &[0; 15]), None);
+        assert_eq!(ExportImage::from_bytes(2, 2, Format::R8G8B8A8Unorm, &[0; 17]), None);
+        assert_eq!(ExportImage::from_bytes(1, 1, Format::R32Sfloat, &[0; 3]), None);
+        assert_eq!(ExportImage::from_bytes(1, 1, Format::D32Sfloat, &[0; 4]), None);
+        // 65536 x 65536 texels overflow u32 but not usize.
+        assert_eq!(ExportImage::from_bytes(65536, 65536, Format::R8Unorm, &[0; 4]), None);
+    }
+
+    #[test]
+    fn converts_to_rgba8() {
+        let image = ExportImage::from_rgba8(1, 1, vec![1, 2, 3, 4]);
+        assert_eq!(image.clone().into_rgba8().unwrap().into_raw(), vec![1, 2, 3, 4]);
+        assert_eq!(image.without_alpha().into_rgba8(), None);
+    }
+}
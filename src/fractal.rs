@@ -0,0 +1,476 @@
+use vulkano::device::{Device,Queue};
+use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder,CommandBuffer};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::{PipelineLayout,PipelineLayoutAbstract};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions,StorageImage};
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::sync::GpuFuture;
+
+use image::{ImageBuffer, Rgba};
+
+use crate::cli::Args;
+use crate::escape_data::EscapeData;
+use crate::palette::{Coloring, LUT_SIZE};
+use crate::readback::ImageDownload;
+use crate::shaders::{fractal_comp as cs, fractal_f64_comp as cs64};
+use crate::supersampling::{Supersampler, Supersampling};
+use crate::tiles::Tile;
+
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fractal {
+    // z = z² + c, starting at z = 0 with c the pixel.
+    Mandelbrot,
+    // z = z² + c, starting at z = the pixel with a fixed c.
+    Julia { c: [f64; 2] },
+    // z = (|Re z| + i|Im z|)² + c.
+    BurningShip,
+    // z = conj(z)² + c.
+    Tricorn,
+    // z = z^exponent + c.
+    Multibrot { exponent: f32 },
+}
+
+impl Default for Fractal {
+    fn default() -> Fractal {
+        Fractal::Mandelbrot
+    }
+}
+
+impl Fractal {
+    // Reads `--fractal mandelbrot|julia|burning-ship|tricorn|multibrot`, with `--julia-c x,y`
+    // and `--exponent d` for the parameterized ones.
+    pub fn from_args(args: &Args) -> Fractal {
+        match args.raw("--fractal").unwrap_or("mandelbrot") {
+            "mandelbrot" => Fractal::Mandelbrot,
+            "julia" => {
+                let (x, y) = args.pair("--julia-c", ',').unwrap_or((-0.8, 0.156));
+                Fractal::Julia { c: [x, y] }
+            },
+            "burning-ship" => Fractal::BurningShip,
+            "tricorn" => Fractal::Tricorn,
+            "multibrot" => Fractal::Multibrot { exponent: args.value("--exponent").unwrap_or(3.0) },
+            other => panic!("unknown fractal `{}`", other),
+        }
+    }
+
+    // The view that shows the whole fractal: real part in [-2, 0] and imaginary part in [-1, 1]
+    // for the Mandelbrot set, as the examples always rendered.
+    pub fn default_center(&self) -> [f64; 2] {
+        match *self {
+            Fractal::Mandelbrot => [-1.0, 0.0],
+            Fractal::BurningShip => [-0.5, -0.5],
+            Fractal::Julia { .. } | Fractal::Tricorn | Fractal::Multibrot { .. } => [0.0, 0.0],
+        }
+    }
+
+    pub fn default_scale(&self) -> f64 {
+        match *self {
+            Fractal::Mandelbrot => 2.0,
+            _ => 3.0,
+        }
+    }
+
+    fn kind(&self) -> u32 {
+        match *self {
+            Fractal::Mandelbrot => 0,
+            Fractal::Julia { .. } => 1,
+            Fractal::BurningShip => 2,
+            Fractal::Tricorn => 3,
+            Fractal::Multibrot { .. } => 4,
+        }
+    }
+}
+
+// `scale` is the height of the view in the complex plane, the width follows from the aspect
+// ratio of the image so pixels are always square.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    pub fractal: Fractal,
+    pub center: [f64; 2],
+    pub scale: f64,
+    pub width: u32,
+    pub height: u32,
+    pub max_iterations: u32,
+}
+
+impl Default for View {
+    fn default() -> View {
+        View::new(Fractal::default())
+    }
+}
+
+impl View {
+    pub fn new(fractal: Fractal) -> View {
+        View {
+            fractal,
+            center: fractal.default_center(),
+            scale: fractal.default_scale(),
+            width: 1024,
+            height: 1024,
+            max_iterations: 200,
+        }
+    }
+
+    // Reads the fractal options, `--center x,y`, `--zoom z` (relative to the default view),
+    // `--scale s`, `--size WxH` and `--iterations n`.
+    pub fn from_args(args: &Args) -> View {
+        let mut view = View::new(Fractal::from_args(args));
+
+        if let Some((x, y)) = args.pair("--center", ',') {
+            view.center = [x, y];
+        }
+        if let Some(zoom) = args.value::<f64>("--zoom") {
+            if !(zoom > 0.0 && zoom.is_finite()) {
+                panic!("--zoom must be a positive number, got {}", zoom);
+            }
+            view.scale = view.fractal.default_scale() / zoom;
+        }
+        if let Some(scale) = args.value::<f64>("--scale") {
+            if !(scale > 0.0 && scale.is_finite()) {
+                panic!("--scale must be a positive number, got {}", scale);
+            }
+            view.scale = scale;
+        }
+        if let Some((width, height)) = args.pair("--size", 'x') {
+            view.width = width;
+            view.height = height;
+        }
+        if let Some(max_iterations) = args.value("--iterations") {
+            view.max_iterations = max_iterations;
+        }
+
+        view
+    }
+
+    pub fn zoom(&self) -> f64 {
+        self.fractal.default_scale() / self.scale
+    }
+
+    pub fn pixel_size(&self) -> f64 {
+        self.scale / self.height as f64
+    }
+
+    // Complex coordinate of the center of pixel (x, y), with the imaginary axis pointing up.
+    pub fn pixel_to_complex(&self, x: f64, y: f64) -> [f64; 2] {
+        [
+            self.center[0] + (x + 0.5 - self.width as f64 * 0.5) * self.pixel_size(),
+            self.center[1] - (y + 0.5 - self.height as f64 * 0.5) * self.pixel_size(),
+        ]
+    }
+
+    // The part of this view covered by `tile`, with the same pixel size.
+    pub fn tile(&self, tile: &Tile) -> View {
+        let pixel_size = self.pixel_size();
+        let x = tile.x as f64 + tile.width as f64 * 0.5 - self.width as f64 * 0.5;
+        let y = tile.y as f64 + tile.height as f64 * 0.5 - self.height as f64 * 0.5;
+
+        View {
+            center: [self.center[0] + x * pixel_size, self.center[1] - y * pixel_size],
+            scale: pixel_size * tile.height as f64,
+            width: tile.width,
+            height: tile.height,
+            .. *self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Single,
+    Double,
+}
+
+impl Precision {
+    // Below this pixel size (relative to the magnitude of the center) neighbouring pixels start
+    // to map to the same f32 coordinate, which shows up as blocky images past about 1e-5 zoom.
+    const SINGLE_LIMIT: f64 = 1e-6;
+
+    // Reads `--precision single|double|auto`, where `auto` (the default) picks double only when
+    // the view is too deep for f32.
+    pub fn from_args(args: &Args, view: &View) -> Precision {
+        match args.raw("--precision").unwrap_or("auto") {
+            "single" => Precision::Single,
+            "double" => Precision::Double,
+            "auto" => Precision::for_view(view),
+            other => panic!("unknown precision `{}`", other),
+        }
+    }
+
+    pub fn for_view(view: &View) -> Precision {
+        let magnitude = view.center[0].abs().max(view.center[1].abs()).max(1.0);
+        if view.pixel_size() / magnitude < Precision::SINGLE_LIMIT {
+            Precision::Double
+        } else {
+            Precision::Single
+        }
+    }
+}
+
+fn fractal_params(view: &View) -> ([f64; 2], f32) {
+    match view.fractal {
+        Fractal::Julia { c } => (c, 2.0),
+        Fractal::Multibrot { exponent } => ([0.0, 0.0], exponent),
+        _ => ([0.0, 0.0], 2.0),
+    }
+}
+
+fn push_constants(view: &View, coloring: &Coloring, sample_offset: [f32; 2], write_raw: bool) -> cs::ty::Params {
+    let (julia_c, exponent) = fractal_params(view);
+
+    cs::ty::Params {
+        center: [view.center[0] as f32, view.center[1] as f32],
+        julia_c: [julia_c[0] as f32, julia_c[1] as f32],
+        interior_color: [coloring.interior[0], coloring.interior[1], coloring.interior[2], 1.0],
+        sample_offset,
+        pixel_size: view.pixel_size() as f32,
+        max_iterations: view.max_iterations,
+        palette_offset: coloring.offset,
+        palette_period: coloring.period.unwrap_or(view.max_iterations.max(1) as f32),
+        smooth_coloring: coloring.smooth as u32,
+        kind: view.fractal.kind(),
+        exponent,
+        write_raw: write_raw as u32,
+    }
+}
+
+fn push_constants64(view: &View, coloring: &Coloring, sample_offset: [f32; 2], write_raw: bool) -> cs64::ty::Params {
+    let (julia_c, exponent) = fractal_params(view);
+
+    cs64::ty::Params {
+        center: view.center,
+        julia_c,
+        interior_color: [coloring.interior[0], coloring.interior[1], coloring.interior[2], 1.0],
+        sample_offset,
+        pixel_size: view.pixel_size(),
+        max_iterations: view.max_iterations,
+        palette_offset: coloring.offset,
+        palette_period: coloring.period.unwrap_or(view.max_iterations.max(1) as f32),
+        smooth_coloring: coloring.smooth as u32,
+        kind: view.fractal.kind(),
+        exponent,
+        write_raw: write_raw as u32,
+    }
+}
+
+enum Pipeline {
+    Single(Arc<ComputePipeline<PipelineLayout<cs::Layout>>>),
+    Double(Arc<ComputePipeline<PipelineLayout<cs64::Layout>>>),
+}
+
+pub struct FractalRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pipeline: Pipeline,
+    local_size: [u32; 3],
+    coloring: Coloring,
+    palette_buffer: Arc<CpuAccessibleBuffer<[[f32; 4]]>>,
+    supersampler: Supersampler,
+    supersampling: Supersampling,
+    // Bound in place of the raw escape data images when they aren't written.
+    no_raw: [Arc<StorageImage<Format>>; 2],
+}
+
+impl FractalRenderer {
+    // Double precision needs the `shader_float64` feature to be enabled on the device, otherwise
+    // the renderer falls back to single precision.
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, local_size: [u32; 3], precision: Precision,
+               cache: Option<Arc<PipelineCache>>) -> FractalRenderer
+    {
+        let precision = if precision == Precision::Double && !device.enabled_features().shader_float64 {
+            println!("Warning: the device doesn't support shader_float64, falling back to single precision");
+            Precision::Single
+        } else {
+            precision
+        };
+
+        let pipeline = match precision {
+            Precision::Single => {
+                let shader = cs::Shader::load(device.clone()).expect("failed to create shader module");
+                let spec_consts = cs::SpecializationConstants {
+                    constant_0: local_size[0],
+                    constant_1: local_size[1],
+                    .. Default::default()
+                };
+
+                Pipeline::Single(Arc::new(
+                    ComputePipeline::new(device.clone(), &shader.main_entry_point(), &spec_consts, cache.clone())
+                            .expect("failed to create compute pipeline")))
+            },
+            Precision::Double => {
+                let shader = cs64::Shader::load(device.clone()).expect("failed to create shader module");
+                let spec_consts = cs64::SpecializationConstants {
+                    constant_0: local_size[0],
+                    constant_1: local_size[1],
+                    .. Default::default()
+                };
+
+                Pipeline::Double(Arc::new(
+                    ComputePipeline::new(device.clone(), &shader.main_entry_point(), &spec_consts, cache.clone())
+                            .expect("failed to create compute pipeline")))
+            },
+        };
+
+        let coloring = Coloring::default();
+        let palette_buffer = upload_palette(&device, &coloring);
+        let supersampler = Supersampler::new(device.clone(), queue.clone(), cache);
+        let no_raw = [create_raw_image(&device, &queue, 1, 1), create_raw_image(&device, &queue, 1, 1)];
+
+        FractalRenderer {
+            device, queue, pipeline, local_size, coloring, palette_buffer, supersampler,
+            supersampling: Supersampling::Off,
+            no_raw,
+        }
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self.pipeline {
+            Pipeline::Single(_) => Precision::Single,
+            Pipeline::Double(_) => Precision::Double,
+        }
+    }
+
+    pub fn coloring(&self) -> &Coloring {
+        &self.coloring
+    }
+
+    pub fn set_coloring(&mut self, coloring: Coloring) {
+        if coloring.palette != self.coloring.palette {
+            self.palette_buffer = upload_palette(&self.device, &coloring);
+        }
+        self.coloring = coloring;
+    }
+
+    pub fn supersampling(&self) -> Supersampling {
+        self.supersampling
+    }
+
+    pub fn set_supersampling(&mut self, supersampling: Supersampling) {
+        self.supersampling = supersampling;
+    }
+
+    pub fn create_image(&self, view: &View) -> Arc<StorageImage<Format>> {
+        StorageImage::new(self.device.clone(), Dimensions::Dim2d { width: view.width, height: view.height },
+                          Format::R8G8B8A8Unorm, Some(self.queue.family())).unwrap()
+    }
+
+    // Records the dispatches that render `view` into `image`, which must be an rgba8 storage image
+    // of `view.width` x `view.height`, one per sample when supersampling.
+    pub fn dispatch(&self, builder: &mut AutoCommandBufferBuilder, image: Arc<StorageImage<Format>>, view: &View) {
+        self.supersampler.record(builder, image, self.supersampling, |builder, image, offset| {
+            self.dispatch_sample(builder, image, view, offset, None);
+        });
+    }
+
+    // `raw` are r32f images for the iteration counts and |z|, written when given.
+    fn dispatch_sample(&self, builder: &mut AutoCommandBufferBuilder, image: Arc<StorageImage<Format>>, view: &View,
+                       sample_offset: [f32; 2], raw: Option<&[Arc<StorageImage<Format>>; 2]>)
+    {
+        let write_raw = raw.is_some();
+        let [raw_iterations, raw_modulus] = raw.unwrap_or(&self.no_raw).clone();
+
+        let groups = [
+            (view.width + self.local_size[0] - 1) / self.local_size[0],
+            (view.height + self.local_size[1] - 1) / self.local_size[1],
+            1,
+        ];
+
+        match self.pipeline {
+            Pipeline::Single(ref pipeline) => {
+                let layout = pipeline.layout().descriptor_set_layout(0).unwrap();
+                let set = Arc::new(
+                    PersistentDescriptorSet::start(layout.clone())
+                    .add_image(image).unwrap()
+                    .add_buffer(self.palette_buffer.clone()).unwrap()
+                    .add_image(raw_iterations).unwrap()
+                    .add_image(raw_modulus).unwrap()
+                    .build().unwrap(),
+                );
+
+                builder
+                    .dispatch(groups, pipeline.clone(), set, push_constants(view, &self.coloring, sample_offset, write_raw)).unwrap();
+            },
+            Pipeline::Double(ref pipeline) => {
+                let layout = pipeline.layout().descriptor_set_layout(0).unwrap();
+                let set = Arc::new(
+                    PersistentDescriptorSet::start(layout.clone())
+                    .add_image(image).unwrap()
+                    .add_buffer(self.palette_buffer.clone()).unwrap()
+                    .add_image(raw_iterations).unwrap()
+                    .add_image(raw_modulus).unwrap()
+                    .build().unwrap(),
+                );
+
+                builder
+                    .dispatch(groups, pipeline.clone(), set, push_constants64(view, &self.coloring, sample_offset, write_raw)).unwrap();
+            },
+        }
+    }
+
+    pub fn render(&self, view: &View) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let image = self.create_image(view);
+
+        let mut builder = AutoCommandBufferBuilder::new(self.device.clone(), self.queue.family()).unwrap();
+        self.dispatch(&mut builder, image.clone(), view);
+        let download = ImageDownload::record(&mut builder, self.device.clone(), image, 0, 0).unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        let finished = command_buffer.execute(self.queue.clone()).unwrap();
+        finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+        download.read().unwrap().into_rgba8().unwrap()
+    }
+
+    // Renders `view` like `render` and also returns the raw iteration count and |z| of every
+    // pixel, taken at the pixel centers even when supersampling.
+    pub fn render_escape_data(&self, view: &View) -> (ImageBuffer<Rgba<u8>, Vec<u8>>, EscapeData) {
+        let image = self.create_image(view);
+        let raw = [
+            create_raw_image(&self.device, &self.queue, view.width, view.height),
+            create_raw_image(&self.device, &self.queue, view.width, view.height),
+        ];
+
+        let mut builder = AutoCommandBufferBuilder::new(self.device.clone(), self.queue.family()).unwrap();
+        if self.supersampling == Supersampling::Off {
+            self.dispatch_sample(&mut builder, image.clone(), view, [0.5, 0.5], Some(&raw));
+        } else {
+            self.dispatch(&mut builder, image.clone(), view);
+            self.dispatch_sample(&mut builder, self.create_image(view), view, [0.5, 0.5], Some(&raw));
+        }
+        let download = ImageDownload::record(&mut builder, self.device.clone(), image, 0, 0).unwrap();
+        let raw_downloads = [
+            ImageDownload::record(&mut builder, self.device.clone(), raw[0].clone(), 0, 0).unwrap(),
+            ImageDownload::record(&mut builder, self.device.clone(), raw[1].clone(), 0, 0).unwrap(),
+        ];
+
+        let command_buffer = builder.build().unwrap();
+
+        let finished = command_buffer.execute(self.queue.clone()).unwrap();
+        finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+        let data = EscapeData {
+            width: view.width,
+            height: view.height,
+            max_iterations: view.max_iterations,
+            iterations: raw_downloads[0].read().unwrap().into_f32().unwrap(),
+            modulus: raw_downloads[1].read().unwrap().into_f32().unwrap(),
+        };
+
+        (download.read().unwrap().into_rgba8().unwrap(), data)
+    }
+}
+
+fn create_raw_image(device: &Arc<Device>, queue: &Arc<Queue>, width: u32, height: u32) -> Arc<StorageImage<Format>> {
+    StorageImage::new(device.clone(), Dimensions::Dim2d { width, height }, Format::R32Sfloat, Some(queue.family()))
+        .unwrap()
+}
+
+fn upload_palette(device: &Arc<Device>, coloring: &Coloring) -> Arc<CpuAccessibleBuffer<[[f32; 4]]>> {
+    CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, coloring.palette.lut(LUT_SIZE).into_iter())
+        .expect("failed to create buffer")
+}
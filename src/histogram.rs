@@ -0,0 +1,160 @@
+use vulkano::device::{Device,Queue};
+use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder,CommandBuffer};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::PipelineLayoutAbstract;
+use vulkano::image::ImageViewAccess;
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+
+use image::{ImageBuffer, ImageResult, Rgb};
+
+use crate::shaders::histogram_comp as cs;
+
+use std::path::Path;
+use std::sync::Arc;
+
+pub const BINS: usize = 256;
+
+// Values are expressed in 8-bit levels (0 ..= 255), the same scale as the histogram bins.
+// Empty images give zeros everywhere.
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelStats {
+    pub histogram: [u32; BINS],
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+    pub variance: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageStats {
+    pub width: u32,
+    pub height: u32,
+    pub channels: [ChannelStats; 4],
+}
+
+impl ChannelStats {
+    fn from_histogram(histogram: [u32; BINS], min: u32, max: u32) -> ChannelStats {
+        let count: f64 = histogram.iter().map(|&n| n as f64).sum();
+        if count == 0.0 {
+            return ChannelStats { histogram, min: 0, max: 0, mean: 0.0, variance: 0.0 };
+        }
+
+        let mean = histogram.iter().enumerate()
+            .map(|(level, &n)| level as f64 * n as f64)
+            .sum::<f64>() / count;
+
+        let variance = histogram.iter().enumerate()
+            .map(|(level, &n)| (level as f64 - mean).powi(2) * n as f64)
+            .sum::<f64>() / count;
+
+        ChannelStats {
+            histogram,
+            min: min as u8,
+            max: max as u8,
+            mean,
+            variance,
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+impl ImageStats {
+    pub fn red(&self) -> &ChannelStats { &self.channels[0] }
+    pub fn green(&self) -> &ChannelStats { &self.channels[1] }
+    pub fn blue(&self) -> &ChannelStats { &self.channels[2] }
+    pub fn alpha(&self) -> &ChannelStats { &self.channels[3] }
+
+    // Draws the red, green and blue histograms on top of each other, so overlapping
+    // bars show up as mixed colors. Alpha is left out because it is usually a single spike.
+    pub fn save_chart<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let width = BINS as u32 * 2;
+        let height = 256;
+
+        let tallest = self.channels[.. 3].iter()
+            .flat_map(|channel| channel.histogram.iter())
+            .cloned()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let chart = ImageBuffer::from_fn(width, height, |x, y| {
+            let bin = (x / 2) as usize;
+            let mut pixel = [0u8; 3];
+            for (ch, value) in pixel.iter_mut().enumerate() {
+                let bar = self.channels[ch].histogram[bin] as u64 * height as u64 / tallest as u64;
+                if (height - y) as u64 <= bar {
+                    *value = 255;
+                }
+            }
+            Rgb(pixel)
+        });
+
+        chart.save(path)
+    }
+}
+
+// Whatever the image format, every channel is clamped to [0, 1] and quantized to the 256 bins,
+// so float images lose their values above 1 and their precision below 1/255.
+pub fn image_stats<I>(device: Arc<Device>, queue: Arc<Queue>, image: Arc<I>, cache: Option<Arc<PipelineCache>>) -> ImageStats
+    where I: ImageViewAccess + Send + Sync + 'static
+{
+    let dimensions = image.dimensions();
+    let (width, height) = (dimensions.width(), dimensions.height());
+    if width == 0 || height == 0 {
+        let empty = ChannelStats::from_histogram([0; BINS], 0, 0);
+        return ImageStats { width, height, channels: [empty; 4] };
+    }
+
+    let shader = cs::Shader::load(device.clone()).expect("failed to create shader module");
+
+    let compute_pipeline = Arc::new(
+        ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), cache)
+                .expect("failed to create compute pipeline"));
+
+    let initial = (0 .. BINS * 4).map(|_| 0u32)
+        .chain((0 .. 4).map(|_| 255u32))
+        .chain((0 .. 4).map(|_| 0u32));
+    let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, initial)
+                .expect("failed to create buffer");
+
+    let sampler = Sampler::simple_repeat_linear_no_mipmap(device.clone());
+
+    let layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap();
+    let set = Arc::new(
+        PersistentDescriptorSet::start(layout.clone())
+        .add_sampled_image(image.clone(), sampler).unwrap()
+        .add_buffer(buf.clone()).unwrap()
+        .build().unwrap(),
+    );
+
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    builder
+        .dispatch([(width + 15) / 16, (height + 15) / 16, 1], compute_pipeline.clone(), set.clone(), ()).unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let finished = command_buffer.execute(queue.clone()).unwrap();
+    finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    let content = buf.read().unwrap();
+    let (bins, extremes) = content.split_at(BINS * 4);
+
+    let channel = |ch: usize| {
+        let mut histogram = [0u32; BINS];
+        histogram.copy_from_slice(&bins[ch * BINS .. (ch + 1) * BINS]);
+        ChannelStats::from_histogram(histogram, extremes[ch], extremes[4 + ch])
+    };
+
+    ImageStats {
+        width,
+        height,
+        channels: [channel(0), channel(1), channel(2), channel(3)],
+    }
+}
@@ -0,0 +1,25 @@
+pub mod animation;
+pub mod camera;
+pub mod cli;
+pub mod cpu_reference;
+pub mod deep_zoom;
+pub mod escape_data;
+pub mod export;
+pub mod fractal;
+pub mod histogram;
+pub mod mesh;
+pub mod obj;
+pub mod palette;
+pub mod pipeline_cache;
+pub mod readback;
+pub mod scene;
+pub mod screenshot;
+pub mod shader_loader;
+pub mod shader_stage;
+pub mod shaders;
+pub mod supersampling;
+pub mod texture;
+pub mod tiles;
+pub mod timing;
+pub mod tuning;
+pub mod video;
@@ -1,11 +1,11 @@
 use vulkano::instance::{Instance,PhysicalDevice};
 use vulkano::device::{Device,DeviceExtensions};
-use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
 use vulkano::framebuffer::{Framebuffer,Subpass,RenderPassAbstract,FramebufferAbstract};
 use vulkano::command_buffer::{AutoCommandBufferBuilder,DynamicState,SubpassContents};
 use vulkano::sync;
 use vulkano::sync::{GpuFuture,FlushError};
-use vulkano::image::{ImageUsage,SwapchainImage};
+use vulkano::format::Format;
+use vulkano::image::{AttachmentImage,SwapchainImage};
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::pipeline::viewport::Viewport;
 use vulkano::swapchain;
@@ -16,12 +16,23 @@ use vulkano_win::VkSurfaceBuild;
 
 use winit::event_loop::{ControlFlow,EventLoop};
 use winit::window::{Window,WindowBuilder};
-use winit::event::{Event, WindowEvent};
+use winit::event::{Event, WindowEvent, ElementState, KeyboardInput, VirtualKeyCode};
 
 use std::sync::Arc;
 
+use vulkano_guide::cli::Args;
+use vulkano_guide::mesh::{Mesh,Topology};
+use vulkano_guide::pipeline_cache::PersistentPipelineCache;
+use vulkano_guide::scene::{GltfScene, SceneRenderer};
+use vulkano_guide::screenshot::{self, Screenshot};
+use vulkano_guide::shaders::{triangle_frag as fs, triangle_vert as vs};
+
 fn main() {
 
+    // `--gltf scene.gltf|scene.glb` draws the scene, from its first camera, instead of the triangle.
+    let args = Args::from_env();
+    let scene = args.value::<String>("--gltf").map(|path| GltfScene::load(&path).unwrap());
+
     let instance = {
         let extensions = vulkano_win::required_extensions();
         Instance::new(None, &extensions, None).expect("failed to create an instance")
@@ -57,14 +68,17 @@ fn main() {
     let alpha = caps.supported_composite_alpha.iter().next().unwrap();
     let format = caps.supported_formats[0].0;
 
-    let (mut swapchain, images) = Swapchain::new(
+    // F12 saves a screenshot, which needs swapchain images that can be copied from.
+    let usage = screenshot::swapchain_usage(&caps);
+
+    let (mut swapchain, mut images) = Swapchain::new(
                                 device.clone(), 
                                 surface.clone(),
                                 caps.min_image_count, 
                                 format, 
                                 dimensions, 
                                 1, 
-                                ImageUsage::color_attachment(), 
+                                usage, 
                                 &queue,
                                 SurfaceTransform::Identity, 
                                 alpha, 
@@ -78,12 +92,10 @@ fn main() {
     let vertex2 = Vertex { position: [ 0.0,  0.5 ]};
     let vertex3 = Vertex { position: [ 0.5, -0.25]};
 
-    let vertex_buffer = CpuAccessibleBuffer::from_iter(
-                            device.clone(), 
-                            BufferUsage::all(), 
-                            false, 
-                            vec![vertex1, vertex2, vertex3].into_iter()
-                        ).unwrap();
+    let mesh = Mesh::new(device.clone(), "triangle", vec![vertex1, vertex2, vertex3].into_iter(),
+                         &[0, 1, 2], Topology::TriangleList);
+
+    let pipeline_cache = PersistentPipelineCache::load(device.clone());
 
     let vs = vs::Shader::load(device.clone()).expect("failed to create vertex shader module");
     let fs = fs::Shader::load(device.clone()).expect("failed to create fragment shader module");
@@ -95,11 +107,17 @@ fn main() {
                                 store: Store,
                                 format: swapchain.format(),
                                 samples: 1,
+                            },
+                            depth: {
+                                load: Clear,
+                                store: DontCare,
+                                format: Format::D16Unorm,
+                                samples: 1,
                             }
                         },
                         pass: {
                             color: [color],
-                            depth_stencil: {}
+                            depth_stencil: {depth}
                         }
                 ).unwrap()
             );
@@ -108,14 +126,26 @@ fn main() {
             GraphicsPipeline::start()
             .vertex_input_single_buffer::<Vertex>()
             .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
+            .primitive_topology(mesh.topology.primitive_topology())
             .viewports_dynamic_scissors_irrelevant(1)
             .fragment_shader(fs.main_entry_point(), ())
             .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build_with_cache(pipeline_cache.cache())
             .build(device.clone())
             .unwrap()
         );
 
+    let mut previous_frame_end = Some(sync::now(device.clone()).boxed());
+
+    let scene = scene.map(|scene| {
+        let camera = scene.default_camera().expect("the scene has nothing to look at");
+        let (renderer, uploaded) = SceneRenderer::new(queue.clone(), &scene, render_pass.clone(),
+                                                      Some(pipeline_cache.cache()));
+        previous_frame_end = Some(uploaded);
+        (renderer, camera)
+    });
+    pipeline_cache.save().expect("failed to save pipeline cache");
+
     let mut dynamic_state = DynamicState {
         line_width: None,
         viewports: None,
@@ -125,11 +155,10 @@ fn main() {
         reference: None,
     };
 
-    let mut framebuffers = window_size_dependent_setup(&images, render_pass.clone(), &mut dynamic_state);
+    let mut framebuffers = window_size_dependent_setup(device.clone(), &images, render_pass.clone(), &mut dynamic_state);
 
     let mut recreate_swapchain = false;
-
-    let mut previous_frame_end = Some(sync::now(device.clone()).boxed());
+    let mut take_screenshot = false;
 
     events_loop.run(move |event, _, control_flow| {
         match event {
@@ -145,6 +174,19 @@ fn main() {
             } => {
                 recreate_swapchain = true;
             }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput {
+                    input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(VirtualKeyCode::F12), .. },
+                    ..
+                },
+                ..
+            } => {
+                if usage.transfer_source {
+                    take_screenshot = true;
+                } else {
+                    println!("The surface doesn't allow copying from swapchain images, can't take screenshots");
+                }
+            }
             Event::RedrawEventsCleared => {
                 previous_frame_end.as_mut().unwrap().cleanup_finished();
 
@@ -158,9 +200,11 @@ fn main() {
                         };
 
                     swapchain = new_swapchain;
+                    images = new_images;
 
                     framebuffers = window_size_dependent_setup(
-                        &new_images, 
+                        device.clone(),
+                        &images, 
                         render_pass.clone(), 
                         &mut dynamic_state
                     );
@@ -181,7 +225,7 @@ fn main() {
                     recreate_swapchain = true;
                 }
 
-                let clear_values = vec![[0.0, 0.0, 1.0, 1.0].into()];
+                let clear_values = vec![[0.0, 0.0, 1.0, 1.0].into(), 1f32.into()];
 
                 let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(
                     device.clone(), 
@@ -190,18 +234,33 @@ fn main() {
 
                 builder
                     .begin_render_pass(framebuffers[image_num].clone(), SubpassContents::Inline, clear_values)
-                    .unwrap()
-                    .draw(
-                        pipeline.clone(),
-                        &dynamic_state,
-                        vertex_buffer.clone(),
-                        (),
-                        (),
-                    )
-                    .unwrap()
+                    .unwrap();
+
+                match scene {
+                    Some((ref renderer, ref camera)) => {
+                        let [width, height] = images[0].dimensions();
+                        renderer.draw(&mut builder, &dynamic_state, camera.view_projection(width as f32 / height as f32));
+                    },
+                    None => mesh.draw(&mut builder, pipeline.clone(), &dynamic_state, (), ()),
+                }
+
+                builder
                     .end_render_pass()
                     .unwrap();
 
+                let screenshot = if take_screenshot {
+                    take_screenshot = false;
+                    match Screenshot::record(&mut builder, device.clone(), images[image_num].clone()) {
+                        Ok(screenshot) => Some(screenshot),
+                        Err(e) => {
+                            println!("Can't take a screenshot: {}", e);
+                            None
+                        },
+                    }
+                } else {
+                    None
+                };
+
                 let command_buffer = builder.build().unwrap();
 
                 let future = previous_frame_end
@@ -215,6 +274,13 @@ fn main() {
 
                 match future {
                     Ok(future) => {
+                        if let Some(screenshot) = screenshot {
+                            future.wait(None).unwrap();
+                            match screenshot.save_timestamped(".") {
+                                Ok(path) => println!("Saved {}", path.display()),
+                                Err(e) => println!("Failed to save screenshot: {}", e),
+                            }
+                        }
                         previous_frame_end = Some(future.boxed());
                     },
                     Err(FlushError::OutOfDate) => {
@@ -239,37 +305,8 @@ struct Vertex {
 
 vulkano::impl_vertex!(Vertex, position);
 
-mod vs {
-    vulkano_shaders::shader! {
-        ty: "vertex",
-        src: "
-#version 450
-
-layout(location = 0) in vec2 position;
-
-void main() {
-    gl_Position = vec4(position, 0.0, 1.0);
-}
-"
-    }
-}
-
-mod fs {
-    vulkano_shaders::shader! {
-        ty: "fragment",
-        src: "
-#version 450
-
-layout(location = 0) out vec4 f_color;
-
-void main() {
-    f_color = vec4(1.0, 0.0, 0.0, 1.0);
-}
-"
-    }
-}
-
 fn window_size_dependent_setup(
+    device: Arc<Device>,
     images: &[Arc<SwapchainImage<Window>>],
     render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
     dynamic_state: &mut DynamicState,
@@ -279,17 +316,21 @@ fn window_size_dependent_setup(
     let viewport = Viewport {
         origin: [0.0, 0.0],
         dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-        depth_range: 0.0..0.1,
+        depth_range: 0.0..1.0,
     };
     dynamic_state.viewports = Some(vec![viewport]);
 
+    let depth = AttachmentImage::transient(device, dimensions, Format::D16Unorm).unwrap();
+
     images.iter()
         .map(|image| {
             Arc::new(
                 Framebuffer::start(render_pass.clone())
                 .add(image.clone())
                 .unwrap()
+                .add(depth.clone())
+                .unwrap()
                 .build()
                 .unwrap(),) as Arc<dyn FramebufferAbstract + Send + Sync>
         }).collect::<Vec<_>>()
-}
\ No newline at end of file
+}
@@ -0,0 +1,232 @@
+use vulkano::device::Device;
+use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer,TypedBufferAccess};
+use vulkano::command_buffer::{AutoCommandBufferBuilder,DynamicState};
+use vulkano::descriptor::descriptor_set::DescriptorSetsCollection;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::pipeline::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::vertex::VertexSource;
+
+use crate::camera::{cross, normalize, sub};
+
+use std::sync::Arc;
+
+// Vertex layout of `shaders/mesh.vert` and `shaders/scene.vert`. Only the second reads `uv`.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    // Linear RGBA, the material's base color.
+    pub color: [f32; 4],
+}
+
+vulkano::impl_vertex!(MeshVertex, position, normal, uv, color);
+
+// How consecutive indices form primitives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topology {
+    PointList,
+    LineList,
+    LineStrip,
+    TriangleList,
+    TriangleStrip,
+    TriangleFan,
+}
+
+impl Default for Topology {
+    fn default() -> Topology {
+        Topology::TriangleList
+    }
+}
+
+impl Topology {
+    pub fn from_name(name: &str) -> Option<Topology> {
+        match name {
+            "points" => Some(Topology::PointList),
+            "lines" => Some(Topology::LineList),
+            "line-strip" => Some(Topology::LineStrip),
+            "triangles" => Some(Topology::TriangleList),
+            "strip" | "triangle-strip" => Some(Topology::TriangleStrip),
+            "fan" | "triangle-fan" => Some(Topology::TriangleFan),
+            _ => None,
+        }
+    }
+
+    pub fn primitive_topology(&self) -> PrimitiveTopology {
+        match *self {
+            Topology::PointList => PrimitiveTopology::PointList,
+            Topology::LineList => PrimitiveTopology::LineList,
+            Topology::LineStrip => PrimitiveTopology::LineStrip,
+            Topology::TriangleList => PrimitiveTopology::TriangleList,
+            Topology::TriangleStrip => PrimitiveTopology::TriangleStrip,
+            Topology::TriangleFan => PrimitiveTopology::TriangleFan,
+        }
+    }
+
+    // The triangles `indices` describe, wound like the first one. Empty for points and lines.
+    pub fn triangles(&self, indices: &[u32]) -> Vec<[u32; 3]> {
+        match *self {
+            Topology::TriangleList => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            Topology::TriangleStrip => indices.windows(3).enumerate()
+                .map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] })
+                .collect(),
+            Topology::TriangleFan => indices.windows(2).skip(1).map(|t| [indices[0], t[0], t[1]]).collect(),
+            Topology::PointList | Topology::LineList | Topology::LineStrip => Vec::new(),
+        }
+    }
+}
+
+// Indexed geometry on the host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    pub topology: Topology,
+}
+
+impl MeshData {
+    // Smooth normals for meshes that come without them: each vertex gets the area weighted
+    // average of the faces around it. Vertices of points and lines, which have no faces, face +Y.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![[0.0f32; 3]; self.vertices.len()];
+        for triangle in self.topology.triangles(&self.indices) {
+            let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            let face = cross(sub(self.vertices[b].position, self.vertices[a].position),
+                             sub(self.vertices[c].position, self.vertices[a].position));
+            for &i in &[a, b, c] {
+                for k in 0 .. 3 {
+                    normals[i][k] += face[k];
+                }
+            }
+        }
+
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = if normal == [0.0; 3] { [0.0, 1.0, 0.0] } else { normalize(normal) };
+        }
+    }
+
+    // Smallest box holding every vertex, None for empty meshes.
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        bounds(self.vertices.iter().map(|v| v.position))
+    }
+
+    pub fn upload(&self, device: Arc<Device>) -> Mesh<MeshVertex> {
+        Mesh::new(device, &self.name, self.vertices.iter().cloned(), &self.indices, self.topology)
+    }
+}
+
+// 16 bit indices when every vertex can be addressed with them, half the size of 32 bit ones.
+pub enum IndexBuffer {
+    U16(Arc<CpuAccessibleBuffer<[u16]>>),
+    U32(Arc<CpuAccessibleBuffer<[u32]>>),
+}
+
+impl IndexBuffer {
+    pub fn new(device: Arc<Device>, indices: &[u32], vertex_count: usize) -> IndexBuffer {
+        if vertex_count <= u16::MAX as usize + 1 {
+            let iter = indices.iter().map(|&i| i as u16);
+            IndexBuffer::U16(CpuAccessibleBuffer::from_iter(device, BufferUsage::index_buffer(), false, iter)
+                                .expect("failed to create buffer"))
+        } else {
+            let iter = indices.iter().cloned();
+            IndexBuffer::U32(CpuAccessibleBuffer::from_iter(device, BufferUsage::index_buffer(), false, iter)
+                                .expect("failed to create buffer"))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match *self {
+            IndexBuffer::U16(ref buffer) => buffer.len(),
+            IndexBuffer::U32(ref buffer) => buffer.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Vertices and the indices connecting them, in GPU buffers. Pipelines drawing a mesh need its
+// topology, `Topology::primitive_topology` gives the one to build them with.
+pub struct Mesh<V> {
+    pub name: String,
+    pub vertex_buffer: Arc<CpuAccessibleBuffer<[V]>>,
+    pub index_buffer: IndexBuffer,
+    pub topology: Topology,
+}
+
+impl<V> Mesh<V> where V: Send + Sync + 'static {
+    pub fn new<I>(device: Arc<Device>, name: &str, vertices: I, indices: &[u32], topology: Topology) -> Mesh<V>
+        where I: ExactSizeIterator<Item = V>
+    {
+        let vertex_count = vertices.len();
+        let vertex_buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::vertex_buffer(), false, vertices)
+                                .expect("failed to create buffer");
+        let index_buffer = IndexBuffer::new(device, indices, vertex_count);
+
+        Mesh { name: name.to_owned(), vertex_buffer, index_buffer, topology }
+    }
+
+    pub fn draw<Gp, S, Pc>(&self, builder: &mut AutoCommandBufferBuilder, pipeline: Gp, dynamic_state: &DynamicState,
+                           sets: S, push_constants: Pc)
+        where Gp: GraphicsPipelineAbstract + VertexSource<Arc<CpuAccessibleBuffer<[V]>>> + Send + Sync + 'static + Clone,
+              S: DescriptorSetsCollection
+    {
+        let vertex_buffer = self.vertex_buffer.clone();
+        match self.index_buffer {
+            IndexBuffer::U16(ref indices) =>
+                builder.draw_indexed(pipeline, dynamic_state, vertex_buffer, indices.clone(), sets, push_constants),
+            IndexBuffer::U32(ref indices) =>
+                builder.draw_indexed(pipeline, dynamic_state, vertex_buffer, indices.clone(), sets, push_constants),
+        }.unwrap();
+    }
+}
+
+pub fn bounds<I: IntoIterator<Item = [f32; 3]>>(points: I) -> Option<([f32; 3], [f32; 3])> {
+    points.into_iter().fold(None, |bounds, p| match bounds {
+        None => Some((p, p)),
+        Some((min, max)) => Some((
+            [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+            [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])],
+        )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_alternate_winding() {
+        let triangles = Topology::TriangleStrip.triangles(&[0, 1, 2, 3, 4]);
+        assert_eq!(triangles, vec![[0, 1, 2], [2, 1, 3], [2, 3, 4]]);
+    }
+
+    #[test]
+    fn fans_share_the_first_vertex() {
+        let triangles = Topology::TriangleFan.triangles(&[0, 1, 2, 3]);
+        assert_eq!(triangles, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn lists_drop_incomplete_triangles() {
+        assert_eq!(Topology::TriangleList.triangles(&[0, 1, 2, 3, 4]), vec![[0, 1, 2]]);
+        assert_eq!(Topology::TriangleStrip.triangles(&[0, 1]), Vec::<[u32; 3]>::new());
+        assert_eq!(Topology::LineStrip.triangles(&[0, 1, 2]), Vec::<[u32; 3]>::new());
+    }
+
+    #[test]
+    fn strip_and_fan_normals_face_the_same_way() {
+        // A counter-clockwise square in the XY plane.
+        let vertex = |x: f32, y: f32| MeshVertex { position: [x, y, 0.0], .. MeshVertex::default() };
+        let square = vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(1.0, 1.0), vertex(0.0, 1.0)];
+
+        for &(topology, ref indices) in [(Topology::TriangleStrip, vec![0, 1, 3, 2]),
+                                         (Topology::TriangleFan, vec![0, 1, 2, 3])].iter() {
+            let mut mesh = MeshData { name: String::new(), vertices: square.clone(), indices: indices.clone(), topology };
+            mesh.compute_normals();
+            assert!(mesh.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]), "{:?}", topology);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::mesh::{self, MeshData, MeshVertex, Topology};
+
+use std::io;
+use std::path::Path;
+
+// Used for objects without a material or when the MTL file can't be read.
+pub const DEFAULT_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+
+// The objects of an OBJ file, triangulated and with one index per vertex, so positions, normals
+// and UVs can share an index buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjScene {
+    pub meshes: Vec<MeshData>,
+}
+
+impl ObjScene {
+    // Loads `path` and the MTL files it references. Each material's diffuse color (and
+    // dissolve, as alpha) becomes the base color of its object's vertices.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<ObjScene> {
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true,
+            .. tobj::LoadOptions::default()
+        };
+
+        let (models, materials) = tobj::load_obj(path.as_ref(), &options)
+                                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let materials = materials.unwrap_or_else(|e| {
+            println!("Ignoring materials of {}: {}", path.as_ref().display(), e);
+            Vec::new()
+        });
+
+        // Objects made only of lines or points have no triangles left, and empty buffers can't be created.
+        let meshes = models.into_iter().filter(|model| !model.mesh.indices.is_empty()).map(|model| {
+            let mesh = model.mesh;
+            let color = mesh.material_id
+                .and_then(|id| materials.get(id))
+                .map_or(DEFAULT_COLOR, |m| [m.diffuse[0], m.diffuse[1], m.diffuse[2], m.dissolve]);
+
+            let vertices = (0 .. mesh.positions.len() / 3).map(|i| MeshVertex {
+                position: [mesh.positions[3 * i], mesh.positions[3 * i + 1], mesh.positions[3 * i + 2]],
+                normal: if mesh.normals.is_empty() {
+                    [0.0; 3]
+                } else {
+                    [mesh.normals[3 * i], mesh.normals[3 * i + 1], mesh.normals[3 * i + 2]]
+                },
+                // OBJ puts the origin of texture coordinates at the bottom left, Vulkan at the top left.
+                uv: if mesh.texcoords.is_empty() {
+                    [0.0; 2]
+                } else {
+                    [mesh.texcoords[2 * i], 1.0 - mesh.texcoords[2 * i + 1]]
+                },
+                color,
+            }).collect();
+
+            let mut data = MeshData { name: model.name, vertices, indices: mesh.indices, topology: Topology::TriangleList };
+            if mesh.normals.is_empty() {
+                data.compute_normals();
+            }
+            data
+        }).collect();
+
+        Ok(ObjScene { meshes })
+    }
+
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        mesh::bounds(self.meshes.iter().flat_map(|m| m.vertices.iter().map(|v| v.position)))
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.meshes.iter().map(|m| m.indices.len() / 3).sum()
+    }
+}
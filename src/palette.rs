@@ -0,0 +1,258 @@
+use crate::cli::Args;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const LUT_SIZE: usize = 256;
+
+// A color gradient over [0, 1] made of sorted stops, with colors in sRGB in [0, 1].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    stops: Vec<(f32, [f32; 3])>,
+}
+
+impl Palette {
+    pub fn from_stops(stops: &[(f32, [f32; 3])]) -> Palette {
+        assert!(!stops.is_empty(), "a palette needs at least one color");
+        assert!(stops.iter().all(|&(pos, _)| pos.is_finite()), "palette stop positions must be finite");
+
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Palette { stops }
+    }
+
+    // Spreads `colors` evenly over [0, 1].
+    pub fn from_colors(colors: &[[f32; 3]]) -> Palette {
+        let last = (colors.len().max(2) - 1) as f32;
+        let stops: Vec<_> = colors.iter().enumerate()
+            .map(|(i, &color)| (i as f32 / last, color))
+            .collect();
+        Palette::from_stops(&stops)
+    }
+
+    pub fn builtin(name: &str) -> Option<Palette> {
+        let rgb = |r: u8, g: u8, b: u8| [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0];
+
+        let palette = match name {
+            "gray" => Palette::from_colors(&[rgb(0, 0, 0), rgb(255, 255, 255)]),
+            "classic" => Palette::from_stops(&[
+                (0.0, rgb(0, 7, 100)),
+                (0.16, rgb(32, 107, 203)),
+                (0.42, rgb(237, 255, 255)),
+                (0.6425, rgb(255, 170, 0)),
+                (0.8575, rgb(0, 2, 0)),
+                (1.0, rgb(0, 7, 100)),
+            ]),
+            "fire" => Palette::from_colors(&[rgb(0, 0, 0), rgb(128, 0, 0), rgb(255, 64, 0), rgb(255, 200, 0), rgb(255, 255, 255)]),
+            "ocean" => Palette::from_colors(&[rgb(0, 0, 32), rgb(0, 64, 128), rgb(0, 192, 224), rgb(224, 255, 255), rgb(0, 0, 32)]),
+            "rainbow" => Palette::from_colors(&[
+                rgb(255, 0, 0), rgb(255, 255, 0), rgb(0, 255, 0), rgb(0, 255, 255),
+                rgb(0, 0, 255), rgb(255, 0, 255), rgb(255, 0, 0),
+            ]),
+            _ => return None,
+        };
+
+        Some(palette)
+    }
+
+    pub fn builtin_names() -> &'static [&'static str] {
+        &["classic", "gray", "fire", "ocean", "rainbow"]
+    }
+
+    // Image files are read as a horizontal strip: every pixel of the middle row becomes a stop.
+    // Anything else is read as text, one color per line as `#rrggbb` or `r g b` (0-255), where
+    // empty lines and lines starting with `//` are ignored.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Palette> {
+        let path = path.as_ref();
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase();
+        if ["png", "jpg", "jpeg", "bmp", "tga"].contains(&extension.as_str()) {
+            let strip = image::open(path).map_err(|e| invalid(e.to_string()))?.to_rgb8();
+            let y = strip.height() / 2;
+            let colors: Vec<_> = (0 .. strip.width())
+                .map(|x| {
+                    let p = strip.get_pixel(x, y);
+                    [p[0] as f32 / 255.0, p[1] as f32 / 255.0, p[2] as f32 / 255.0]
+                })
+                .collect();
+
+            if colors.is_empty() {
+                return Err(invalid(format!("{:?} is empty", path)));
+            }
+            return Ok(Palette::from_colors(&colors));
+        }
+
+        let mut colors = Vec::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let color = parse_color(line)
+                .ok_or_else(|| invalid(format!("{:?}:{}: invalid color `{}`", path, number + 1, line)))?;
+            colors.push(color);
+        }
+
+        if colors.is_empty() {
+            return Err(invalid(format!("{:?} has no colors", path)));
+        }
+        Ok(Palette::from_colors(&colors))
+    }
+
+    // A built-in name or a path to a palette file.
+    pub fn by_name_or_path(name: &str) -> io::Result<Palette> {
+        match Palette::builtin(name) {
+            Some(palette) => Ok(palette),
+            None => Palette::load(name),
+        }
+    }
+
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let t = t.max(0.0).min(1.0);
+
+        let next = self.stops.iter().position(|&(pos, _)| pos >= t).unwrap_or(self.stops.len() - 1);
+        if next == 0 {
+            return self.stops[0].1;
+        }
+
+        let (p0, c0) = self.stops[next - 1];
+        let (p1, c1) = self.stops[next];
+        let f = if p1 > p0 { (t - p0) / (p1 - p0) } else { 0.0 };
+
+        [
+            c0[0] + (c1[0] - c0[0]) * f,
+            c0[1] + (c1[1] - c0[1]) * f,
+            c0[2] + (c1[2] - c0[2]) * f,
+        ]
+    }
+
+    // Evenly resampled colors as uploaded to the shaders, vec4-aligned.
+    pub fn lut(&self, size: usize) -> Vec<[f32; 4]> {
+        (0 .. size)
+            .map(|i| {
+                let c = self.sample(i as f32 / (size - 1) as f32);
+                [c[0], c[1], c[2], 1.0]
+            })
+            .collect()
+    }
+}
+
+pub fn parse_color(text: &str) -> Option<[f32; 3]> {
+    let text = text.trim();
+
+    let bytes = if text.starts_with('#') {
+        let hex = &text[1 ..];
+        // Checked before slicing, multi-byte characters would split.
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(&hex[i .. i + 2], 16).ok();
+        [byte(0)?, byte(2)?, byte(4)?]
+    } else {
+        let values: Vec<u8> = text.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|v| !v.is_empty())
+            .map(|v| v.parse().ok())
+            .collect::<Option<_>>()?;
+        if values.len() != 3 {
+            return None;
+        }
+        [values[0], values[1], values[2]]
+    };
+
+    Some([bytes[0] as f32 / 255.0, bytes[1] as f32 / 255.0, bytes[2] as f32 / 255.0])
+}
+
+// How escape times are turned into colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coloring {
+    pub palette: Palette,
+    // Normalized iteration count instead of the integer escape time, removes banding.
+    pub smooth: bool,
+    // Shifts the palette, in palette cycles. Animating it cycles the colors.
+    pub offset: f32,
+    // Iterations per palette cycle, `None` stretches one cycle over the maximum iteration count.
+    pub period: Option<f32>,
+    pub interior: [f32; 3],
+}
+
+impl Default for Coloring {
+    fn default() -> Coloring {
+        Coloring {
+            palette: Palette::builtin("classic").unwrap(),
+            smooth: true,
+            offset: 0.0,
+            period: None,
+            interior: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl Coloring {
+    // Reads `--palette name|file`, `--palette-offset t`, `--palette-period n`,
+    // `--interior #rrggbb` and `--no-smooth`.
+    pub fn from_args(args: &Args) -> Coloring {
+        let mut coloring = Coloring::default();
+
+        if let Some(name) = args.raw("--palette") {
+            coloring.palette = Palette::by_name_or_path(name)
+                .unwrap_or_else(|e| panic!("failed to load palette {}: {}", name, e));
+        }
+        if let Some(offset) = args.value("--palette-offset") {
+            coloring.offset = offset;
+        }
+        if let Some(period) = args.value::<f32>("--palette-period") {
+            // The shaders divide by the period.
+            if !(period > 0.0 && period.is_finite()) {
+                panic!("--palette-period must be a positive number, got {}", period);
+            }
+            coloring.period = Some(period);
+        }
+        if let Some(interior) = args.raw("--interior") {
+            coloring.interior = parse_color(interior)
+                .unwrap_or_else(|| panic!("invalid interior color `{}`", interior));
+        }
+        if args.flag("--no-smooth") {
+            coloring.smooth = false;
+        }
+
+        coloring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_and_decimal_colors() {
+        assert_eq!(parse_color("#ff8000"), Some([1.0, 128.0 / 255.0, 0.0]));
+        assert_eq!(parse_color("  #FF8000 "), Some([1.0, 128.0 / 255.0, 0.0]));
+        assert_eq!(parse_color("255 128 0"), Some([1.0, 128.0 / 255.0, 0.0]));
+        assert_eq!(parse_color("255, 128, 0"), Some([1.0, 128.0 / 255.0, 0.0]));
+    }
+
+    #[test]
+    fn rejects_bad_colors() {
+        for text in ["", "#", "#ff80", "#ff80000", "#gg8000", "#+f8000", "#aé123", "#éééé", "255 128",
+                     "255 128 0 0", "256 0 0", "-1 0 0", "red"].iter() {
+            assert_eq!(parse_color(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn sorts_stops() {
+        let palette = Palette::from_stops(&[(1.0, [1.0; 3]), (0.0, [0.0; 3])]);
+        assert_eq!(palette.sample(0.0), [0.0; 3]);
+        assert_eq!(palette.sample(0.5), [0.5; 3]);
+        assert_eq!(palette.sample(1.0), [1.0; 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "finite")]
+    fn rejects_nan_stops() {
+        Palette::from_stops(&[(0.0, [0.0; 3]), (f32::NAN, [1.0; 3])]);
+    }
+}
@@ -0,0 +1,116 @@
+use vulkano::device::Device;
+use vulkano::pipeline::cache::PipelineCache;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"VKGC";
+const HEADER_LEN: usize = 4 + 4 + 16;
+
+// A PipelineCache backed by a file in the cache directory. The file name is derived from the
+// device's pipeline cache UUID and driver version, and the same values are stored in a small
+// header so that a file that doesn't match the current device is discarded instead of loaded.
+pub struct PersistentPipelineCache {
+    device: Arc<Device>,
+    cache: Arc<PipelineCache>,
+    path: PathBuf,
+}
+
+impl PersistentPipelineCache {
+    pub fn load(device: Arc<Device>) -> PersistentPipelineCache {
+        PersistentPipelineCache::load_from(device, cache_dir())
+    }
+
+    pub fn load_from<P: AsRef<Path>>(device: Arc<Device>, dir: P) -> PersistentPipelineCache {
+        let prefix = hex(device.physical_device().uuid());
+        let path = dir.as_ref().join(format!("{}.bin", device_key(&device)));
+
+        remove_stale(dir.as_ref(), &prefix, &path);
+
+        let cache = match fs::read(&path) {
+            Ok(ref data) if data.len() >= HEADER_LEN && data[.. HEADER_LEN] == header(&device)[..] => {
+                unsafe { PipelineCache::with_data(device.clone(), &data[HEADER_LEN ..]) }
+                    .expect("failed to create pipeline cache")
+            },
+            Ok(_) => {
+                println!("Discarding pipeline cache {:?}: it was created for another device or driver", path);
+                let _ = fs::remove_file(&path);
+                PipelineCache::empty(device.clone()).expect("failed to create pipeline cache")
+            },
+            Err(_) => PipelineCache::empty(device.clone()).expect("failed to create pipeline cache"),
+        };
+
+        PersistentPipelineCache { device, cache, path }
+    }
+
+    pub fn cache(&self) -> Arc<PipelineCache> {
+        self.cache.clone()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let data = self.cache.get_data()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = header(&self.device).to_vec();
+        contents.extend_from_slice(&data);
+
+        // Write to a temporary file first so that a crash never leaves a truncated cache behind.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+// Uses $VULKANO_GUIDE_CACHE_DIR, or `pipeline_cache/` in the working directory.
+pub fn cache_dir() -> PathBuf {
+    env::var_os("VULKANO_GUIDE_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("pipeline_cache"))
+}
+
+// Identifies the device and driver pair that produced a cached result.
+pub fn device_key(device: &Device) -> String {
+    let physical = device.physical_device();
+    format!("{}-{}", hex(physical.uuid()), physical.driver_version())
+}
+
+fn header(device: &Arc<Device>) -> [u8; HEADER_LEN] {
+    let physical = device.physical_device();
+
+    let mut header = [0u8; HEADER_LEN];
+    header[.. 4].copy_from_slice(MAGIC);
+    header[4 .. 8].copy_from_slice(&physical.driver_version().to_le_bytes());
+    header[8 ..].copy_from_slice(physical.uuid());
+    header
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Caches written by an older driver for the same device will never be valid again.
+fn remove_stale(dir: &Path, prefix: &str, current: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let is_same_device = path.file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with(prefix))
+            .unwrap_or(false);
+
+        if is_same_device && path != current {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
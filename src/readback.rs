@@ -0,0 +1,128 @@
+use vulkano::device::{Device,Queue};
+use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder,CommandBuffer};
+use vulkano::format::Format;
+use vulkano::image::ImageAccess;
+use vulkano::sync::GpuFuture;
+
+use image::DynamicImage;
+
+use crate::export::{ExportImage, OutputFormat};
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReadbackError {
+    NoMipLevel(u32),
+    NoArrayLayer(u32),
+    // Depth, compressed and packed formats, or anything `ExportImage::from_bytes` can't read.
+    UnsupportedFormat(Format),
+}
+
+impl fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadbackError::NoMipLevel(level) => write!(f, "the image has no mip level {}", level),
+            ReadbackError::NoArrayLayer(layer) => write!(f, "the image has no array layer {}", layer),
+            ReadbackError::UnsupportedFormat(format) => write!(f, "can't read back images with format {:?}", format),
+        }
+    }
+}
+
+impl error::Error for ReadbackError {}
+
+impl From<ReadbackError> for io::Error {
+    fn from(e: ReadbackError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+// The copy of one mip level of one array layer of an image to a host visible buffer, recorded
+// into a command buffer. `read` gives the pixels once that command buffer has finished.
+pub struct ImageDownload {
+    buf: Arc<CpuAccessibleBuffer<[u8]>>,
+    width: u32,
+    height: u32,
+    format: Format,
+}
+
+impl ImageDownload {
+    // The image needs the `transfer_source` usage and a color format supported by
+    // `ExportImage::from_bytes`.
+    pub fn record<I>(builder: &mut AutoCommandBufferBuilder, device: Arc<Device>, image: Arc<I>, mip_level: u32,
+                     layer: u32) -> Result<ImageDownload, ReadbackError>
+        where I: ImageAccess + Send + Sync + 'static
+    {
+        let dimensions = image.dimensions();
+        if mip_level >= image.mipmap_levels() {
+            return Err(ReadbackError::NoMipLevel(mip_level));
+        }
+        if layer >= dimensions.array_layers() {
+            return Err(ReadbackError::NoArrayLayer(layer));
+        }
+
+        let width = (dimensions.width() >> mip_level).max(1);
+        let height = (dimensions.height() >> mip_level).max(1);
+
+        let format = image.format();
+        let texel_size = readable_texel_size(format).ok_or(ReadbackError::UnsupportedFormat(format))?;
+
+        let iter = (0 .. width as usize * height as usize * texel_size).map(|_| 0u8);
+        let buf = CpuAccessibleBuffer::from_iter(device, BufferUsage::all(), false, iter)
+                    .expect("failed to create buffer");
+
+        builder
+            .copy_image_to_buffer_dimensions(image, buf.clone(), [0, 0, 0], [width, height, 1], layer, 1, mip_level)
+            .unwrap();
+
+        Ok(ImageDownload { buf, width, height, format })
+    }
+
+    pub fn read(&self) -> Result<ExportImage, ReadbackError> {
+        let buffer_content = self.buf.read().unwrap();
+        ExportImage::from_bytes(self.width, self.height, self.format, &buffer_content)
+            .ok_or(ReadbackError::UnsupportedFormat(self.format))
+    }
+}
+
+// Size of a texel of `format`, if it has a fixed size and `ExportImage::from_bytes` reads it.
+fn readable_texel_size(format: Format) -> Option<usize> {
+    let size = format.size()?;
+    ExportImage::from_bytes(1, 1, format, &vec![0; size]).map(|_| size)
+}
+
+// Copies one mip level of one array layer of `image` back to the host and waits for it.
+pub fn download<I>(device: Arc<Device>, queue: Arc<Queue>, image: Arc<I>, mip_level: u32,
+                   layer: u32) -> Result<ExportImage, ReadbackError>
+    where I: ImageAccess + Send + Sync + 'static
+{
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    let download = ImageDownload::record(&mut builder, device, image, mip_level, layer)?;
+
+    let command_buffer = builder.build().unwrap();
+
+    let finished = command_buffer.execute(queue).unwrap();
+    finished.then_signal_fence_and_flush().unwrap().wait(None).unwrap();
+
+    download.read()
+}
+
+// Mip level 0 of layer 0 as a `DynamicImage`. Float formats become 16-bit sRGB encoded images.
+pub fn download_image<I>(device: Arc<Device>, queue: Arc<Queue>, image: Arc<I>) -> Result<DynamicImage, ReadbackError>
+    where I: ImageAccess + Send + Sync + 'static
+{
+    download(device, queue, image, 0, 0).map(|image| image.to_dynamic_image())
+}
+
+// Downloads mip level 0 of layer 0 and saves it, in the format given by the extension of `path`.
+// Readback errors come back as `InvalidInput` io errors.
+pub fn save_image<I, P>(device: Arc<Device>, queue: Arc<Queue>, image: Arc<I>, path: P) -> io::Result<()>
+    where I: ImageAccess + Send + Sync + 'static, P: AsRef<Path>
+{
+    let format = OutputFormat::from_path(path.as_ref()).unwrap_or(OutputFormat::Png);
+    download(device, queue, image, 0, 0)?.save(path, format)
+}
@@ -0,0 +1,455 @@
+use vulkano::device::Queue;
+use vulkano::command_buffer::{AutoCommandBufferBuilder,DynamicState};
+use vulkano::descriptor::DescriptorSet;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::PipelineLayoutAbstract;
+use vulkano::framebuffer::{RenderPassAbstract,Subpass};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::sampler::{Filter,MipmapMode,Sampler,SamplerAddressMode};
+use vulkano::sync::{self,GpuFuture};
+
+use gltf::camera::Projection;
+use gltf::mesh::Mode;
+use gltf::texture::{MagFilter,MinFilter,WrappingMode};
+
+use crate::camera::{self, Camera};
+use crate::mesh::{self, Mesh, MeshData, MeshVertex, Topology};
+use crate::shaders::{scene_frag as fs, scene_vert as vs};
+use crate::texture::{self, TextureData, TextureFormat, TexturePixels};
+
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureSampler {
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub mipmaps: bool,
+    pub wrap_u: SamplerAddressMode,
+    pub wrap_v: SamplerAddressMode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneTexture {
+    // Index into `GltfScene::images`.
+    pub image: usize,
+    pub sampler: TextureSampler,
+}
+
+// The metallic-roughness model is reduced to its base color, which is all the scene shaders use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneMaterial {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    // Index into `GltfScene::textures`.
+    pub base_color_texture: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenePrimitive {
+    // Vertex colors hold COLOR_0 times the material's base color factor.
+    pub data: MeshData,
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<ScenePrimitive>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneNode {
+    pub name: Option<String>,
+    // Relative to the parent, and the product of every transform up to the root.
+    pub local: [[f32; 4]; 4],
+    pub world: [[f32; 4]; 4],
+    pub mesh: Option<usize>,
+    pub camera: Option<Camera>,
+    pub children: Vec<usize>,
+}
+
+// A glTF 2.0 file (`.gltf` with its buffers and images, or `.glb`) read into host memory. Only
+// the default scene, or the first one, is kept; nodes are indexed as in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfScene {
+    pub meshes: Vec<SceneMesh>,
+    pub materials: Vec<SceneMaterial>,
+    pub textures: Vec<SceneTexture>,
+    pub images: Vec<TextureData>,
+    pub nodes: Vec<SceneNode>,
+    pub roots: Vec<usize>,
+}
+
+impl GltfScene {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<GltfScene> {
+        let (document, buffers, images) = gltf::import(path).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let images = images.into_iter().map(image_data).collect();
+
+        let textures = document.textures().map(|texture| {
+            let sampler = texture.sampler();
+            let filter = |linear| if linear { Filter::Linear } else { Filter::Nearest };
+            let (min_linear, mipmaps) = match sampler.min_filter() {
+                Some(MinFilter::Nearest) => (false, false),
+                Some(MinFilter::Linear) => (true, false),
+                Some(MinFilter::NearestMipmapNearest) | Some(MinFilter::NearestMipmapLinear) => (false, true),
+                Some(MinFilter::LinearMipmapNearest) | Some(MinFilter::LinearMipmapLinear) | None => (true, true),
+            };
+
+            SceneTexture {
+                image: texture.source().index(),
+                sampler: TextureSampler {
+                    mag_filter: filter(sampler.mag_filter() != Some(MagFilter::Nearest)),
+                    min_filter: filter(min_linear),
+                    mipmaps,
+                    wrap_u: address_mode(sampler.wrap_s()),
+                    wrap_v: address_mode(sampler.wrap_t()),
+                },
+            }
+        }).collect();
+
+        let materials: Vec<_> = document.materials().map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            SceneMaterial {
+                name: material.name().map(str::to_owned),
+                base_color: pbr.base_color_factor(),
+                base_color_texture: pbr.base_color_texture().map(|info| info.texture().index()),
+            }
+        }).collect();
+
+        let meshes = document.meshes().map(|mesh| {
+            let primitives = mesh.primitives().filter_map(|primitive| {
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()].0[..]));
+                let positions: Vec<[f32; 3]> = reader.read_positions()?.collect();
+                let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+                let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+                let colors: Option<Vec<[f32; 4]>> = reader.read_colors(0).map(|c| c.into_rgba_f32().collect());
+
+                let material = primitive.material().index();
+                let factor = material.map_or([1.0; 4], |i| materials[i].base_color);
+
+                let vertices = positions.iter().enumerate().map(|(i, &position)| {
+                    let color = colors.as_ref().map_or([1.0; 4], |c| c[i]);
+                    MeshVertex {
+                        position,
+                        normal: normals.as_ref().map_or([0.0; 3], |n| n[i]),
+                        uv: uvs.as_ref().map_or([0.0; 2], |t| t[i]),
+                        color: [color[0] * factor[0], color[1] * factor[1], color[2] * factor[2], color[3] * factor[3]],
+                    }
+                }).collect();
+                let mut indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0 .. positions.len() as u32).collect(),
+                };
+                // Nothing to draw, and empty buffers can't be created.
+                if positions.is_empty() || indices.is_empty() {
+                    return None;
+                }
+
+                // Vulkan has no line loops, a strip back to the first index closes them.
+                let topology = match primitive.mode() {
+                    Mode::Points => Topology::PointList,
+                    Mode::Lines => Topology::LineList,
+                    Mode::LineLoop => {
+                        if let Some(&first) = indices.first() {
+                            indices.push(first);
+                        }
+                        Topology::LineStrip
+                    },
+                    Mode::LineStrip => Topology::LineStrip,
+                    Mode::Triangles => Topology::TriangleList,
+                    Mode::TriangleStrip => Topology::TriangleStrip,
+                    Mode::TriangleFan => Topology::TriangleFan,
+                };
+
+                let name = mesh.name().unwrap_or_default().to_owned();
+                let mut data = MeshData { name, vertices, indices, topology };
+                if normals.is_none() {
+                    data.compute_normals();
+                }
+                Some(ScenePrimitive { data, material })
+            }).collect();
+
+            SceneMesh { name: mesh.name().map(str::to_owned), primitives }
+        }).collect();
+
+        let mut nodes: Vec<_> = document.nodes().map(|node| {
+            let camera = node.camera().and_then(|camera| match camera.projection() {
+                Projection::Perspective(perspective) => Some(Camera {
+                    eye: [0.0; 3],
+                    target: [0.0, 0.0, -1.0],
+                    up: [0.0, 1.0, 0.0],
+                    fov_y: perspective.yfov(),
+                    near: perspective.znear(),
+                    far: perspective.zfar().unwrap_or(perspective.znear() * 1e5),
+                }),
+                Projection::Orthographic(_) => {
+                    println!("Skipping orthographic camera {}, only perspective cameras are supported", camera.index());
+                    None
+                },
+            });
+
+            SceneNode {
+                name: node.name().map(str::to_owned),
+                local: node.transform().matrix(),
+                world: IDENTITY,
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                camera,
+                children: node.children().map(|child| child.index()).collect(),
+            }
+        }).collect();
+
+        let roots: Vec<_> = document.default_scene().or_else(|| document.scenes().next())
+                                .map_or_else(Vec::new, |scene| scene.nodes().map(|node| node.index()).collect());
+
+        // World transforms, and cameras placed where their nodes are. glTF cameras look down -Z
+        // with +Y up.
+        let mut stack: Vec<_> = roots.iter().map(|&root| (root, IDENTITY)).collect();
+        while let Some((index, parent)) = stack.pop() {
+            let node = &mut nodes[index];
+            node.world = camera::multiply(&parent, &node.local);
+
+            if let Some(ref mut camera) = node.camera {
+                camera.eye = transform_point(&node.world, [0.0; 3]);
+                camera.target = transform_point(&node.world, [0.0, 0.0, -1.0]);
+                camera.up = transform_vector(&node.world, [0.0, 1.0, 0.0]);
+            }
+
+            let world = node.world;
+            stack.extend(node.children.iter().map(|&child| (child, world)));
+        }
+
+        Ok(GltfScene { meshes, materials, textures, images, nodes, roots })
+    }
+
+    // Nodes of the scene that have a mesh, with the index of the mesh and where it's placed.
+    pub fn instances(&self) -> Vec<(usize, [[f32; 4]; 4])> {
+        let mut instances = Vec::new();
+        let mut stack = self.roots.clone();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if let Some(mesh) = node.mesh {
+                instances.push((mesh, node.world));
+            }
+            stack.extend(node.children.iter().cloned());
+        }
+        instances
+    }
+
+    // Cameras of the scene, in node order.
+    pub fn cameras(&self) -> Vec<Camera> {
+        let mut visible = vec![false; self.nodes.len()];
+        let mut stack = self.roots.clone();
+        while let Some(index) = stack.pop() {
+            visible[index] = true;
+            stack.extend(self.nodes[index].children.iter().cloned());
+        }
+
+        self.nodes.iter().zip(visible).filter(|(_, visible)| *visible).filter_map(|(node, _)| node.camera).collect()
+    }
+
+    // Bounds of every placed mesh, in world space.
+    pub fn bounds(&self) -> Option<([f32; 3], [f32; 3])> {
+        let instances = self.instances();
+        let points = instances.iter().flat_map(|(mesh, world)| {
+            self.meshes[*mesh].primitives.iter()
+                .flat_map(|primitive| primitive.data.vertices.iter())
+                .map(move |vertex| transform_point(world, vertex.position))
+        });
+        mesh::bounds(points)
+    }
+
+    // The first camera of the file, or one framing the whole scene from the default angle.
+    pub fn default_camera(&self) -> Option<Camera> {
+        self.cameras().into_iter().next()
+            .or_else(|| self.bounds().map(|(min, max)| Camera::framing(min, max, 30.0, 20.0)))
+    }
+}
+
+fn image_data(data: gltf::image::Data) -> TextureData {
+    use gltf::image::Format;
+
+    // Channels and bytes per channel. 16 bit images keep their high byte.
+    let (channels, size, bgr) = match data.format {
+        Format::R8 => (1, 1, false),
+        Format::R8G8 => (2, 1, false),
+        Format::R8G8B8 => (3, 1, false),
+        Format::R8G8B8A8 => (4, 1, false),
+        Format::B8G8R8 => (3, 1, true),
+        Format::B8G8R8A8 => (4, 1, true),
+        Format::R16 => (1, 2, false),
+        Format::R16G16 => (2, 2, false),
+        Format::R16G16B16 => (3, 2, false),
+        Format::R16G16B16A16 => (4, 2, false),
+    };
+
+    let sample = |texel: &[u8], channel: usize| {
+        let i = channel * size;
+        if size == 2 { (u16::from_ne_bytes([texel[i], texel[i + 1]]) >> 8) as u8 } else { texel[i] }
+    };
+
+    let mut rgba = Vec::with_capacity((data.width * data.height * 4) as usize);
+    for texel in data.pixels.chunks(channels * size) {
+        let [r, g, b, a] = match channels {
+            1 => { let l = sample(texel, 0); [l, l, l, 255] },
+            2 => { let l = sample(texel, 0); [l, l, l, sample(texel, 1)] },
+            3 => [sample(texel, 0), sample(texel, 1), sample(texel, 2), 255],
+            _ => [sample(texel, 0), sample(texel, 1), sample(texel, 2), sample(texel, 3)],
+        };
+        if bgr { rgba.extend_from_slice(&[b, g, r, a]) } else { rgba.extend_from_slice(&[r, g, b, a]) }
+    }
+
+    TextureData { width: data.width, height: data.height, pixels: TexturePixels::U8(rgba) }
+}
+
+fn address_mode(mode: WrappingMode) -> SamplerAddressMode {
+    match mode {
+        WrappingMode::ClampToEdge => SamplerAddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
+        WrappingMode::Repeat => SamplerAddressMode::Repeat,
+    }
+}
+
+pub fn transform_point(m: &[[f32; 4]; 4], p: [f32; 3]) -> [f32; 3] {
+    let v = transform_vector(m, p);
+    [v[0] + m[3][0], v[1] + m[3][1], v[2] + m[3][2]]
+}
+
+pub fn transform_vector(m: &[[f32; 4]; 4], v: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * v[0] + m[1][0] * v[1] + m[2][0] * v[2],
+        m[0][1] * v[0] + m[1][1] * v[1] + m[2][1] * v[2],
+        m[0][2] * v[0] + m[1][2] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+type ScenePipeline = GraphicsPipeline<SingleBufferDefinition<MeshVertex>, Box<dyn PipelineLayoutAbstract + Send + Sync>,
+                                      Arc<dyn RenderPassAbstract + Send + Sync>>;
+
+// A `GltfScene` in GPU buffers and images, drawn with `shaders/scene.vert` and `scene.frag`.
+pub struct SceneRenderer {
+    // One per topology used by the scene, triangle lists always first.
+    pipelines: Vec<(Topology, Arc<ScenePipeline>)>,
+    // Per mesh, each primitive with the index of its material's descriptor set.
+    meshes: Vec<Vec<(Mesh<MeshVertex>, usize)>>,
+    // One per material, then one for primitives without a material.
+    sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+    instances: Vec<(usize, [[f32; 4]; 4])>,
+}
+
+impl SceneRenderer {
+    // Uploads the scene and builds a pipeline for subpass 0 of `render_pass`, which needs a
+    // depth attachment. The returned future finishes the texture uploads; chain the first frame
+    // after it.
+    pub fn new(queue: Arc<Queue>, scene: &GltfScene, render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+               cache: Option<Arc<PipelineCache>>) -> (SceneRenderer, Box<dyn GpuFuture>)
+    {
+        let device = queue.device().clone();
+
+        let vs = vs::Shader::load(device.clone()).expect("failed to create vertex shader module");
+        let fs = fs::Shader::load(device.clone()).expect("failed to create fragment shader module");
+
+        let mut topologies = vec![Topology::TriangleList];
+        for primitive in scene.meshes.iter().flat_map(|mesh| mesh.primitives.iter()) {
+            if !topologies.contains(&primitive.data.topology) {
+                topologies.push(primitive.data.topology);
+            }
+        }
+
+        let pipelines: Vec<_> = topologies.into_iter().map(|topology| {
+            let builder = GraphicsPipeline::start()
+                .vertex_input_single_buffer::<MeshVertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .primitive_topology(topology.primitive_topology())
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(fs.main_entry_point(), ())
+                .depth_stencil_simple_depth()
+                .blend_alpha_blending()
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap());
+            let builder = match cache.clone() {
+                Some(cache) => builder.build_with_cache(cache),
+                None => builder,
+            };
+            let pipeline: ScenePipeline = builder.build(device.clone()).unwrap();
+            (topology, Arc::new(pipeline))
+        }).collect();
+
+        // Images are uploaded once however many textures use them, with mipmaps when any does.
+        let mut future = sync::now(device.clone()).boxed();
+        let mut images = Vec::with_capacity(scene.images.len());
+        for (i, data) in scene.images.iter().enumerate() {
+            let mipmaps = scene.textures.iter().any(|t| t.image == i && t.sampler.mipmaps);
+            let (image, uploaded) = texture::upload(queue.clone(), data, TextureFormat::Srgb, mipmaps);
+            future = future.join(uploaded).boxed();
+            images.push(image);
+        }
+
+        let white = TextureData { width: 1, height: 1, pixels: TexturePixels::U8(vec![255; 4]) };
+        let (white, uploaded) = texture::upload(queue.clone(), &white, TextureFormat::Srgb, false);
+        future = future.join(uploaded).boxed();
+
+        // Every pipeline uses the same shaders, so their sets are interchangeable.
+        let layout = pipelines[0].1.layout().descriptor_set_layout(0).unwrap();
+        let mut sets: Vec<Arc<dyn DescriptorSet + Send + Sync>> = Vec::new();
+        for material in scene.materials.iter().map(Some).chain(Some(None)) {
+            let texture = material.and_then(|m| m.base_color_texture).map(|t| &scene.textures[t]);
+            let (image, sampler) = match texture {
+                Some(texture) => (images[texture.image].clone(), create_sampler(&queue, &texture.sampler)),
+                None => (white.clone(), create_sampler(&queue, &TextureSampler {
+                    mag_filter: Filter::Nearest,
+                    min_filter: Filter::Nearest,
+                    mipmaps: false,
+                    wrap_u: SamplerAddressMode::Repeat,
+                    wrap_v: SamplerAddressMode::Repeat,
+                })),
+            };
+
+            sets.push(Arc::new(
+                PersistentDescriptorSet::start(layout.clone())
+                .add_sampled_image(image, sampler).unwrap()
+                .build().unwrap(),
+            ));
+        }
+
+        let default_set = scene.materials.len();
+        let meshes = scene.meshes.iter().map(|mesh| {
+            mesh.primitives.iter()
+                .map(|primitive| (primitive.data.upload(device.clone()), primitive.material.unwrap_or(default_set)))
+                .collect()
+        }).collect();
+
+        let renderer = SceneRenderer { pipelines, meshes, sets, instances: scene.instances() };
+        (renderer, future)
+    }
+
+    // Records every mesh instance. Must be called inside subpass 0 of the render pass given to `new`.
+    pub fn draw(&self, builder: &mut AutoCommandBufferBuilder, dynamic_state: &DynamicState,
+                view_projection: [[f32; 4]; 4]) {
+        for &(mesh, model) in self.instances.iter() {
+            let push_constants = vs::ty::Transforms { view_projection, model };
+
+            for (primitive, set) in self.meshes[mesh].iter() {
+                let pipeline = &self.pipelines.iter().find(|(t, _)| *t == primitive.topology).unwrap().1;
+                primitive.draw(builder, pipeline.clone(), dynamic_state, self.sets[*set].clone(), push_constants);
+            }
+        }
+    }
+}
+
+fn create_sampler(queue: &Queue, sampler: &TextureSampler) -> Arc<Sampler> {
+    let (mipmap_mode, max_lod) = if sampler.mipmaps { (MipmapMode::Linear, 1000.0) } else { (MipmapMode::Nearest, 0.0) };
+    Sampler::new(queue.device().clone(), sampler.mag_filter, sampler.min_filter, mipmap_mode,
+                 sampler.wrap_u, sampler.wrap_v, SamplerAddressMode::Repeat, 0.0, 1.0, 0.0, max_lod)
+        .expect("failed to create sampler")
+}
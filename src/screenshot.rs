@@ -0,0 +1,104 @@
+use vulkano::device::Device;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::image::{ImageAccess,ImageUsage};
+use vulkano::swapchain::Capabilities;
+
+use crate::export::OutputFormat;
+use crate::readback::{ImageDownload, ReadbackError};
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Usage for swapchain images that can be captured: color attachment, plus transfer source when
+// the surface allows it.
+pub fn swapchain_usage(caps: &Capabilities) -> ImageUsage {
+    ImageUsage {
+        color_attachment: true,
+        transfer_source: caps.supported_usage_flags.transfer_source,
+        .. ImageUsage::none()
+    }
+}
+
+// A copy of what was rendered to a swapchain image (or any other color target), recorded after
+// the frame's render pass so it holds exactly what gets presented.
+pub struct Screenshot {
+    download: ImageDownload,
+}
+
+impl Screenshot {
+    // Fails for swapchain formats that can't be read back, like the packed 10-bit and half float
+    // formats of HDR surfaces.
+    pub fn record<I>(builder: &mut AutoCommandBufferBuilder, device: Arc<Device>,
+                     image: Arc<I>) -> Result<Screenshot, ReadbackError>
+        where I: ImageAccess + Send + Sync + 'static
+    {
+        Ok(Screenshot { download: ImageDownload::record(builder, device, image, 0, 0)? })
+    }
+
+    // Saves as PNG once the frame's command buffer has finished. BGRA swapchain formats are
+    // swapped to RGBA and alpha is dropped, since the compositor ignores it anyway.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.download.read()?.without_alpha().save(path, OutputFormat::Png)
+    }
+
+    // Saves as `screenshot-<UTC date and time>.png` inside `dir` and returns the path.
+    pub fn save_timestamped<P: AsRef<Path>>(&self, dir: P) -> io::Result<PathBuf> {
+        let path = dir.as_ref().join(timestamped_name(SystemTime::now()));
+        self.save(&path)?;
+        Ok(path)
+    }
+}
+
+// `screenshot-2021-01-31_18-04-05.123.png`, sorting in capture order.
+pub fn timestamped_name(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let seconds_of_day = seconds % 86400;
+
+    format!("screenshot-{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.{:03}.png", year, month, day,
+            seconds_of_day / 3600, seconds_of_day / 60 % 60, seconds_of_day % 60, since_epoch.subsec_millis())
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian date, after Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn names_sort_by_capture_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_612_116_245_123);
+        assert_eq!(timestamped_name(time), "screenshot-2021-01-31_18-04-05.123.png");
+
+        let time = UNIX_EPOCH + Duration::from_secs(951_868_799);
+        assert_eq!(timestamped_name(time), "screenshot-2000-02-29_23-59-59.000.png");
+    }
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(18_321), (2020, 2, 29));
+        assert_eq!(civil_from_days(18_322), (2020, 3, 1));
+        // 2100 isn't a leap year.
+        assert_eq!(civil_from_days(47_540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+    }
+}
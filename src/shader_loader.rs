@@ -0,0 +1,783 @@
+use vulkano::descriptor::descriptor::{DescriptorBufferDesc, DescriptorDesc, DescriptorDescTy, DescriptorImageDesc,
+                                      DescriptorImageDescArray, DescriptorImageDescDimensions, ShaderStages};
+use vulkano::descriptor::pipeline_layout::{PipelineLayout, PipelineLayoutDesc, PipelineLayoutDescPcRange};
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::shader::{GraphicsEntryPoint, GraphicsShaderType, ShaderInterfaceDef, ShaderInterfaceDefEntry,
+                                ShaderModule};
+use vulkano::pipeline::{ComputePipeline, ComputePipelineCreationError};
+use vulkano::OomError;
+
+use crate::shader_stage;
+pub use crate::shader_stage::ShaderStage;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum ShaderLoadError {
+    Io(io::Error),
+    UnknownStage(String),
+    Compile(String),
+    InvalidSpirv(&'static str),
+    Oom(OomError),
+}
+
+impl fmt::Display for ShaderLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderLoadError::Io(ref e) => write!(f, "failed to read shader: {}", e),
+            ShaderLoadError::UnknownStage(ref reason) => write!(f, "{}", reason),
+            ShaderLoadError::Compile(ref log) => write!(f, "failed to compile shader:\n{}", log),
+            ShaderLoadError::InvalidSpirv(reason) => write!(f, "invalid SPIR-V: {}", reason),
+            ShaderLoadError::Oom(ref e) => write!(f, "failed to create shader module: {}", e),
+        }
+    }
+}
+
+impl error::Error for ShaderLoadError {}
+
+impl From<io::Error> for ShaderLoadError {
+    fn from(e: io::Error) -> ShaderLoadError {
+        ShaderLoadError::Io(e)
+    }
+}
+
+impl From<OomError> for ShaderLoadError {
+    fn from(e: OomError) -> ShaderLoadError {
+        ShaderLoadError::Oom(e)
+    }
+}
+
+// Descriptor layout reflected from the SPIR-V, the runtime equivalent of the `Layout` type
+// that `vulkano_shaders::shader!` generates.
+#[derive(Debug, Clone)]
+pub struct RuntimeLayout {
+    sets: Vec<Vec<Option<DescriptorDesc>>>,
+    push_constants: Option<PipelineLayoutDescPcRange>,
+}
+
+unsafe impl PipelineLayoutDesc for RuntimeLayout {
+    fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.sets.get(set).map(|bindings| bindings.len())
+    }
+
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.sets.get(set).and_then(|bindings| bindings.get(binding)).and_then(|desc| desc.clone())
+    }
+
+    fn num_push_constants_ranges(&self) -> usize {
+        if self.push_constants.is_some() { 1 } else { 0 }
+    }
+
+    fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
+        if num == 0 { self.push_constants } else { None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeInterface {
+    entries: Vec<ShaderInterfaceDefEntry>,
+}
+
+unsafe impl ShaderInterfaceDef for RuntimeInterface {
+    type Iter = std::vec::IntoIter<ShaderInterfaceDefEntry>;
+
+    fn elements(&self) -> Self::Iter {
+        self.entries.clone().into_iter()
+    }
+}
+
+pub struct RuntimeShader {
+    module: Arc<ShaderModule>,
+    stage: ShaderStage,
+    entry_point: CString,
+    layout: RuntimeLayout,
+    inputs: RuntimeInterface,
+    outputs: RuntimeInterface,
+    local_size: [u32; 3],
+}
+
+impl RuntimeShader {
+    // `.comp`, `.vert` and `.frag` files (or `.glsl` ones with a `#pragma shader_stage(...)`) are
+    // compiled as GLSL, `.spv` files are loaded as they are and their stage is taken from the
+    // entry point. Stages are told apart the same way build.rs does.
+    pub fn load<P: AsRef<Path>>(device: Arc<Device>, path: P) -> Result<RuntimeShader, ShaderLoadError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+
+        if extension == "spv" {
+            return RuntimeShader::from_spirv(device, &fs::read(path)?);
+        }
+
+        let source = fs::read_to_string(path)?;
+        let stage = shader_stage::declared_stage(&source, path).map_err(ShaderLoadError::UnknownStage)?;
+
+        RuntimeShader::from_glsl(device, &source, stage, &path.to_string_lossy())
+    }
+
+    pub fn from_glsl(device: Arc<Device>, source: &str, stage: ShaderStage, file_name: &str)
+        -> Result<RuntimeShader, ShaderLoadError>
+    {
+        let artifact = compile_glsl(source, stage, file_name)?;
+        RuntimeShader::from_spirv(device, artifact.as_binary_u8())
+    }
+
+    pub fn from_spirv(device: Arc<Device>, bytes: &[u8]) -> Result<RuntimeShader, ShaderLoadError> {
+        let reflection = Reflection::parse(bytes)?;
+        let module = unsafe { ShaderModule::new(device, bytes)? };
+
+        Ok(RuntimeShader {
+            module,
+            stage: reflection.stage,
+            entry_point: reflection.entry_point.clone(),
+            layout: reflection.layout(),
+            inputs: reflection.interface(STORAGE_INPUT),
+            outputs: reflection.interface(STORAGE_OUTPUT),
+            local_size: reflection.local_size,
+        })
+    }
+
+    pub fn stage(&self) -> ShaderStage {
+        self.stage
+    }
+
+    pub fn layout(&self) -> &RuntimeLayout {
+        &self.layout
+    }
+
+    pub fn local_size(&self) -> [u32; 3] {
+        self.local_size
+    }
+
+    pub fn compute_pipeline(&self, device: Arc<Device>, cache: Option<Arc<PipelineCache>>)
+        -> Result<Arc<ComputePipeline<PipelineLayout<RuntimeLayout>>>, ComputePipelineCreationError>
+    {
+        assert_eq!(self.stage, ShaderStage::Compute, "not a compute shader");
+
+        let entry_point = unsafe {
+            self.module.compute_entry_point::<(), _>(&self.entry_point, self.layout.clone())
+        };
+
+        Ok(Arc::new(ComputePipeline::new(device, &entry_point, &(), cache)?))
+    }
+
+    pub fn graphics_entry_point(&self) -> GraphicsEntryPoint<(), RuntimeInterface, RuntimeInterface, RuntimeLayout> {
+        let ty = match self.stage {
+            ShaderStage::Vertex => GraphicsShaderType::Vertex,
+            ShaderStage::Fragment => GraphicsShaderType::Fragment,
+            ShaderStage::Compute => panic!("not a graphics shader"),
+            // `Reflection::parse` rejects the other stages.
+            stage => unreachable!("{:?} shaders can't be loaded at runtime", stage),
+        };
+
+        unsafe {
+            self.module.graphics_entry_point(&self.entry_point, self.inputs.clone(), self.outputs.clone(),
+                                             self.layout.clone(), ty)
+        }
+    }
+}
+
+// `shaders/include/` of this crate, where build.rs looks for includes too.
+fn include_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders").join("include")
+}
+
+// `#include`s are resolved like in build.rs, relative to `file_name` and then in `include_dir()`.
+fn compile_glsl(source: &str, stage: ShaderStage, file_name: &str)
+    -> Result<shaderc::CompilationArtifact, ShaderLoadError>
+{
+    let mut compiler = shaderc::Compiler::new().expect("failed to initialize shaderc");
+    let options = shader_stage::compile_options(include_dir()).expect("failed to initialize shaderc");
+    compiler.compile_into_spirv(source, stage.shaderc_kind(), file_name, "main", Some(&options))
+        .map_err(|e| ShaderLoadError::Compile(e.to_string()))
+}
+
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_BUILTIN: u32 = 11;
+const DECORATION_NON_WRITABLE: u32 = 24;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+#[derive(Debug, Clone)]
+enum Type {
+    Scalar { float: bool, signed: bool, width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, arrayed: bool, multisampled: bool, sampled: u32, format: u32 },
+    Sampler,
+    SampledImage(u32),
+    Array { element: u32, length: u32 },
+    RuntimeArray(u32),
+    Struct(Vec<u32>),
+    Pointer { storage: u32, pointee: u32 },
+}
+
+struct Reflection {
+    stage: ShaderStage,
+    entry_point: CString,
+    local_size: [u32; 3],
+    names: HashMap<u32, String>,
+    decorations: HashMap<u32, Vec<(u32, u32)>>,
+    member_offsets: HashMap<(u32, u32), u32>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl Reflection {
+    fn parse(bytes: &[u8]) -> Result<Reflection, ShaderLoadError> {
+        if bytes.len() % 4 != 0 || bytes.len() < 20 {
+            return Err(ShaderLoadError::InvalidSpirv("size is not a whole number of words"));
+        }
+
+        let mut words: Vec<u32> = bytes.chunks(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+
+        match words[0] {
+            0x07230203 => (),
+            0x03022307 => words.iter_mut().for_each(|w| *w = w.swap_bytes()),
+            _ => return Err(ShaderLoadError::InvalidSpirv("bad magic number")),
+        }
+
+        let mut reflection = Reflection {
+            stage: ShaderStage::Compute,
+            entry_point: CString::new("main").unwrap(),
+            local_size: [1, 1, 1],
+            names: HashMap::new(),
+            decorations: HashMap::new(),
+            member_offsets: HashMap::new(),
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            variables: Vec::new(),
+        };
+        let mut found_entry_point = false;
+
+        let mut i = 5;
+        while i < words.len() {
+            let opcode = words[i] & 0xffff;
+            let count = (words[i] >> 16) as usize;
+            if count == 0 || i + count > words.len() {
+                return Err(ShaderLoadError::InvalidSpirv("truncated instruction"));
+            }
+            let ops = &words[i + 1 .. i + count];
+            if ops.len() < min_operands(opcode, ops) {
+                return Err(ShaderLoadError::InvalidSpirv("instruction has too few operands"));
+            }
+
+            match opcode {
+                OP_NAME => {
+                    reflection.names.insert(ops[0], literal_string(&ops[1 ..]));
+                },
+                OP_ENTRY_POINT if !found_entry_point => {
+                    reflection.stage = match ShaderStage::from_execution_model(ops[0]) {
+                        Some(stage @ ShaderStage::Vertex) | Some(stage @ ShaderStage::Fragment)
+                            | Some(stage @ ShaderStage::Compute) => stage,
+                        _ => return Err(ShaderLoadError::InvalidSpirv("unsupported execution model")),
+                    };
+                    reflection.entry_point = CString::new(literal_string(&ops[2 ..])).unwrap();
+                    found_entry_point = true;
+                },
+                OP_EXECUTION_MODE if ops[1] == EXECUTION_MODE_LOCAL_SIZE => {
+                    reflection.local_size = [ops[2], ops[3], ops[4]];
+                },
+                OP_TYPE_INT => {
+                    reflection.types.insert(ops[0], Type::Scalar { float: false, signed: ops[2] != 0, width: ops[1] });
+                },
+                OP_TYPE_FLOAT => {
+                    reflection.types.insert(ops[0], Type::Scalar { float: true, signed: true, width: ops[1] });
+                },
+                OP_TYPE_VECTOR => {
+                    reflection.types.insert(ops[0], Type::Vector { component: ops[1], count: ops[2] });
+                },
+                OP_TYPE_MATRIX => {
+                    reflection.types.insert(ops[0], Type::Matrix { column: ops[1], count: ops[2] });
+                },
+                OP_TYPE_IMAGE => {
+                    reflection.types.insert(ops[0], Type::Image {
+                        dim: ops[2],
+                        arrayed: ops[4] != 0,
+                        multisampled: ops[5] != 0,
+                        sampled: ops[6],
+                        format: ops[7],
+                    });
+                },
+                OP_TYPE_SAMPLER => {
+                    reflection.types.insert(ops[0], Type::Sampler);
+                },
+                OP_TYPE_SAMPLED_IMAGE => {
+                    reflection.types.insert(ops[0], Type::SampledImage(ops[1]));
+                },
+                OP_TYPE_ARRAY => {
+                    reflection.types.insert(ops[0], Type::Array { element: ops[1], length: ops[2] });
+                },
+                OP_TYPE_RUNTIME_ARRAY => {
+                    reflection.types.insert(ops[0], Type::RuntimeArray(ops[1]));
+                },
+                OP_TYPE_STRUCT => {
+                    reflection.types.insert(ops[0], Type::Struct(ops[1 ..].to_vec()));
+                },
+                OP_TYPE_POINTER => {
+                    reflection.types.insert(ops[0], Type::Pointer { storage: ops[1], pointee: ops[2] });
+                },
+                OP_CONSTANT => {
+                    reflection.constants.insert(ops[1], ops[2]);
+                },
+                OP_VARIABLE => {
+                    reflection.variables.push((ops[1], ops[0], ops[2]));
+                },
+                OP_DECORATE => {
+                    let value = ops.get(2).cloned().unwrap_or(0);
+                    reflection.decorations.entry(ops[0]).or_insert_with(Vec::new).push((ops[1], value));
+                },
+                OP_MEMBER_DECORATE => {
+                    if ops[2] == DECORATION_OFFSET {
+                        reflection.member_offsets.insert((ops[0], ops[1]), ops[3]);
+                    }
+                    if ops[2] == DECORATION_BUILTIN {
+                        reflection.decorations.entry(ops[0]).or_insert_with(Vec::new).push((DECORATION_BUILTIN, ops[3]));
+                    }
+                },
+                _ => (),
+            }
+
+            i += count;
+        }
+
+        if !found_entry_point {
+            return Err(ShaderLoadError::InvalidSpirv("no entry point"));
+        }
+
+        Ok(reflection)
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations.get(&id)
+            .and_then(|list| list.iter().find(|&&(d, _)| d == decoration))
+            .map(|&(_, value)| value)
+    }
+
+    fn pointee(&self, pointer: u32) -> Option<u32> {
+        match self.types.get(&pointer) {
+            Some(&Type::Pointer { pointee, .. }) => Some(pointee),
+            _ => None,
+        }
+    }
+
+    fn layout(&self) -> RuntimeLayout {
+        let stages = shader_stages(self.stage);
+        let mut sets: Vec<Vec<Option<DescriptorDesc>>> = Vec::new();
+        let mut push_constants = None;
+
+        for &(id, pointer, storage) in self.variables.iter() {
+            let ty = match self.pointee(pointer) {
+                Some(ty) => ty,
+                None => continue,
+            };
+
+            if storage == STORAGE_PUSH_CONSTANT {
+                push_constants = Some(PipelineLayoutDescPcRange {
+                    offset: 0,
+                    size: self.size_of(ty) as usize,
+                    stages,
+                });
+                continue;
+            }
+
+            if storage != STORAGE_UNIFORM_CONSTANT && storage != STORAGE_UNIFORM && storage != STORAGE_STORAGE_BUFFER {
+                continue;
+            }
+
+            let (set, binding) = match (self.decoration(id, DECORATION_DESCRIPTOR_SET), self.decoration(id, DECORATION_BINDING)) {
+                (Some(set), Some(binding)) => (set as usize, binding as usize),
+                _ => continue,
+            };
+
+            let (ty, array_count) = match self.types.get(&ty) {
+                Some(&Type::Array { element, length }) => (element, self.constants.get(&length).cloned().unwrap_or(1)),
+                _ => (ty, 1),
+            };
+
+            let readonly = self.decoration(id, DECORATION_NON_WRITABLE).is_some();
+            let desc_ty = match self.descriptor_ty(ty, storage) {
+                Some(desc_ty) => desc_ty,
+                None => continue,
+            };
+
+            if sets.len() <= set {
+                sets.resize(set + 1, Vec::new());
+            }
+            if sets[set].len() <= binding {
+                sets[set].resize(binding + 1, None);
+            }
+            sets[set][binding] = Some(DescriptorDesc {
+                ty: desc_ty,
+                array_count,
+                stages,
+                readonly,
+            });
+        }
+
+        RuntimeLayout { sets, push_constants }
+    }
+
+    fn descriptor_ty(&self, ty: u32, storage: u32) -> Option<DescriptorDescTy> {
+        if storage == STORAGE_STORAGE_BUFFER {
+            return Some(DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage: true }));
+        }
+
+        match *self.types.get(&ty)? {
+            Type::Struct(_) => {
+                let storage = self.decoration(ty, DECORATION_BUFFER_BLOCK).is_some();
+                debug_assert!(storage || self.decoration(ty, DECORATION_BLOCK).is_some());
+                Some(DescriptorDescTy::Buffer(DescriptorBufferDesc { dynamic: Some(false), storage }))
+            },
+            Type::Sampler => Some(DescriptorDescTy::Sampler),
+            Type::SampledImage(image) => match self.descriptor_ty(image, storage)? {
+                DescriptorDescTy::Image(desc) => Some(DescriptorDescTy::CombinedImageSampler(desc)),
+                _ => None,
+            },
+            Type::Image { dim: 5, sampled, format, .. } => Some(DescriptorDescTy::TexelBuffer {
+                storage: sampled == 2,
+                format: image_format(format),
+            }),
+            Type::Image { dim: 6, multisampled, .. } => Some(DescriptorDescTy::InputAttachment {
+                multisampled,
+                array_layers: DescriptorImageDescArray::NonArrayed,
+            }),
+            Type::Image { dim, arrayed, multisampled, sampled, format } => Some(DescriptorDescTy::Image(DescriptorImageDesc {
+                sampled: sampled != 2,
+                dimensions: match dim {
+                    0 => DescriptorImageDescDimensions::OneDimensional,
+                    2 => DescriptorImageDescDimensions::ThreeDimensional,
+                    3 => DescriptorImageDescDimensions::Cube,
+                    _ => DescriptorImageDescDimensions::TwoDimensional,
+                },
+                format: image_format(format),
+                multisampled,
+                array_layers: if arrayed {
+                    DescriptorImageDescArray::Arrayed { max_layers: None }
+                } else {
+                    DescriptorImageDescArray::NonArrayed
+                },
+            })),
+            _ => None,
+        }
+    }
+
+    fn size_of(&self, ty: u32) -> u32 {
+        match self.types.get(&ty) {
+            Some(&Type::Scalar { width, .. }) => width / 8,
+            Some(&Type::Vector { component, count }) => self.size_of(component) * count,
+            Some(&Type::Matrix { column, count }) => self.size_of(column) * count,
+            Some(&Type::Array { element, length }) => {
+                let stride = self.decoration(ty, DECORATION_ARRAY_STRIDE).unwrap_or_else(|| self.size_of(element));
+                stride * self.constants.get(&length).cloned().unwrap_or(1)
+            },
+            Some(&Type::Struct(ref members)) => {
+                members.iter().enumerate()
+                    .map(|(m, &member)| {
+                        self.member_offsets.get(&(ty, m as u32)).cloned().unwrap_or(0) + self.size_of(member)
+                    })
+                    .max()
+                    .unwrap_or(0)
+            },
+            _ => 0,
+        }
+    }
+
+    fn interface(&self, storage_class: u32) -> RuntimeInterface {
+        let mut entries = Vec::new();
+
+        for &(id, pointer, storage) in self.variables.iter() {
+            if storage != storage_class || self.decoration(id, DECORATION_BUILTIN).is_some() {
+                continue;
+            }
+
+            let (location, ty) = match (self.decoration(id, DECORATION_LOCATION), self.pointee(pointer)) {
+                (Some(location), Some(ty)) => (location, ty),
+                _ => continue,
+            };
+
+            let (format, locations) = match self.types.get(&ty) {
+                Some(&Type::Matrix { column, count }) => (self.vertex_format(column), count),
+                _ => (self.vertex_format(ty), 1),
+            };
+
+            if let Some(format) = format {
+                entries.push(ShaderInterfaceDefEntry {
+                    location: location .. location + locations,
+                    format,
+                    name: self.names.get(&id).map(|name| Cow::Owned(name.clone())),
+                });
+            }
+        }
+
+        RuntimeInterface { entries }
+    }
+
+    fn vertex_format(&self, ty: u32) -> Option<Format> {
+        let (component, count) = match self.types.get(&ty)? {
+            &Type::Vector { component, count } => (component, count),
+            _ => (ty, 1),
+        };
+
+        match (self.types.get(&component)?, count) {
+            (&Type::Scalar { float: true, width: 32, .. }, 1) => Some(Format::R32Sfloat),
+            (&Type::Scalar { float: true, width: 32, .. }, 2) => Some(Format::R32G32Sfloat),
+            (&Type::Scalar { float: true, width: 32, .. }, 3) => Some(Format::R32G32B32Sfloat),
+            (&Type::Scalar { float: true, width: 32, .. }, 4) => Some(Format::R32G32B32A32Sfloat),
+            (&Type::Scalar { float: false, signed: true, width: 32 }, 1) => Some(Format::R32Sint),
+            (&Type::Scalar { float: false, signed: true, width: 32 }, 2) => Some(Format::R32G32Sint),
+            (&Type::Scalar { float: false, signed: true, width: 32 }, 3) => Some(Format::R32G32B32Sint),
+            (&Type::Scalar { float: false, signed: true, width: 32 }, 4) => Some(Format::R32G32B32A32Sint),
+            (&Type::Scalar { float: false, signed: false, width: 32 }, 1) => Some(Format::R32Uint),
+            (&Type::Scalar { float: false, signed: false, width: 32 }, 2) => Some(Format::R32G32Uint),
+            (&Type::Scalar { float: false, signed: false, width: 32 }, 3) => Some(Format::R32G32B32Uint),
+            (&Type::Scalar { float: false, signed: false, width: 32 }, 4) => Some(Format::R32G32B32A32Uint),
+            _ => None,
+        }
+    }
+}
+
+fn shader_stages(stage: ShaderStage) -> ShaderStages {
+    match stage {
+        ShaderStage::Vertex => ShaderStages { vertex: true, .. ShaderStages::none() },
+        ShaderStage::TessControl => ShaderStages { tessellation_control: true, .. ShaderStages::none() },
+        ShaderStage::TessEvaluation => ShaderStages { tessellation_evaluation: true, .. ShaderStages::none() },
+        ShaderStage::Geometry => ShaderStages { geometry: true, .. ShaderStages::none() },
+        ShaderStage::Fragment => ShaderStages { fragment: true, .. ShaderStages::none() },
+        ShaderStage::Compute => ShaderStages { compute: true, .. ShaderStages::none() },
+    }
+}
+
+// Operands the parser reads from each instruction it handles. Execution modes and member
+// decorations only have their extra operands when they are the kind the parser looks at.
+fn min_operands(opcode: u32, ops: &[u32]) -> usize {
+    match opcode {
+        OP_TYPE_SAMPLER | OP_TYPE_STRUCT => 1,
+        OP_NAME | OP_TYPE_FLOAT | OP_TYPE_SAMPLED_IMAGE | OP_TYPE_RUNTIME_ARRAY | OP_DECORATE => 2,
+        OP_ENTRY_POINT | OP_TYPE_INT | OP_TYPE_VECTOR | OP_TYPE_MATRIX | OP_TYPE_ARRAY | OP_TYPE_POINTER
+            | OP_CONSTANT | OP_VARIABLE => 3,
+        OP_TYPE_IMAGE => 8,
+        OP_EXECUTION_MODE => if ops.get(1) == Some(&EXECUTION_MODE_LOCAL_SIZE) { 5 } else { 2 },
+        OP_MEMBER_DECORATE => match ops.get(2) {
+            Some(&DECORATION_OFFSET) | Some(&DECORATION_BUILTIN) => 4,
+            _ => 3,
+        },
+        _ => 0,
+    }
+}
+
+fn literal_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words.iter()
+        .flat_map(|w| w.to_le_bytes().to_vec())
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn image_format(format: u32) -> Option<Format> {
+    match format {
+        1 => Some(Format::R32G32B32A32Sfloat),
+        2 => Some(Format::R16G16B16A16Sfloat),
+        3 => Some(Format::R32Sfloat),
+        4 => Some(Format::R8G8B8A8Unorm),
+        6 => Some(Format::R32G32Sfloat),
+        10 => Some(Format::R16G16B16A16Unorm),
+        33 => Some(Format::R32Uint),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A SPIR-V module with a valid header, each instruction given as its opcode and operands.
+    fn module(instructions: &[&[u32]]) -> Vec<u8> {
+        let mut words = vec![0x07230203, 0x00010000, 0, 100, 0];
+        for instruction in instructions {
+            words.push((instruction.len() as u32) << 16 | instruction[0]);
+            words.extend_from_slice(&instruction[1 ..]);
+        }
+        words.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect()
+    }
+
+    const MAIN: u32 = 0x6e69616d;
+    const ENTRY_POINT: &[u32] = &[OP_ENTRY_POINT, 5, 1, MAIN, 0];
+
+    fn invalid(result: Result<Reflection, ShaderLoadError>) -> bool {
+        match result {
+            Err(ShaderLoadError::InvalidSpirv(_)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn parses_entry_point_and_local_size() {
+        let bytes = module(&[ENTRY_POINT, &[OP_EXECUTION_MODE, 1, EXECUTION_MODE_LOCAL_SIZE, 8, 4, 1]]);
+        let reflection = Reflection::parse(&bytes).unwrap();
+
+        assert_eq!(reflection.stage, ShaderStage::Compute);
+        assert_eq!(reflection.entry_point.to_str().unwrap(), "main");
+        assert_eq!(reflection.local_size, [8, 4, 1]);
+    }
+
+    #[test]
+    fn rejects_instructions_without_operands() {
+        let opcodes = [
+            OP_NAME, OP_ENTRY_POINT, OP_EXECUTION_MODE, OP_TYPE_INT, OP_TYPE_FLOAT, OP_TYPE_VECTOR,
+            OP_TYPE_MATRIX, OP_TYPE_IMAGE, OP_TYPE_SAMPLER, OP_TYPE_SAMPLED_IMAGE, OP_TYPE_ARRAY,
+            OP_TYPE_RUNTIME_ARRAY, OP_TYPE_STRUCT, OP_TYPE_POINTER, OP_CONSTANT, OP_VARIABLE,
+            OP_DECORATE, OP_MEMBER_DECORATE,
+        ];
+        for &opcode in opcodes.iter() {
+            let bytes = module(&[ENTRY_POINT, &[opcode]]);
+            assert!(invalid(Reflection::parse(&bytes)), "opcode {} without operands was accepted", opcode);
+        }
+    }
+
+    #[test]
+    fn rejects_short_operand_lists() {
+        let short: &[&[u32]] = &[
+            &[OP_EXECUTION_MODE, 1, EXECUTION_MODE_LOCAL_SIZE, 8, 4],
+            &[OP_TYPE_IMAGE, 2, 3, 1, 0, 0, 0, 2],
+            &[OP_MEMBER_DECORATE, 4, 0, DECORATION_OFFSET],
+            &[OP_TYPE_POINTER, 5, STORAGE_UNIFORM],
+        ];
+        for &instruction in short.iter() {
+            assert!(invalid(Reflection::parse(&module(&[ENTRY_POINT, instruction]))));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_modules() {
+        let bytes = module(&[ENTRY_POINT, &[OP_EXECUTION_MODE, 1, EXECUTION_MODE_LOCAL_SIZE, 8, 4, 1]]);
+        for len in 0 .. bytes.len() {
+            let truncated = &bytes[.. len];
+            // Cutting between instructions leaves a valid module, only without an execution mode.
+            if len == 20 + 4 * ENTRY_POINT.len() {
+                assert!(Reflection::parse(truncated).is_ok());
+            } else {
+                assert!(invalid(Reflection::parse(truncated)), "{} bytes were accepted", len);
+            }
+        }
+    }
+
+    #[test]
+    fn survives_shrunk_word_counts() {
+        let source = "
+#version 450
+layout(local_size_x = 16, local_size_y = 16) in;
+layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+layout(set = 0, binding = 1) buffer Data { vec4 values[]; } data;
+layout(push_constant) uniform Params { mat4 transform; float scale; } params;
+void main() {
+    vec4 value = params.transform * data.values[gl_GlobalInvocationID.x] * params.scale;
+    imageStore(img, ivec2(gl_GlobalInvocationID.xy), value);
+}
+";
+        let mut compiler = shaderc::Compiler::new().unwrap();
+        let artifact = compiler.compile_into_spirv(source, shaderc::ShaderKind::Compute, "test.comp", "main", None)
+            .unwrap();
+        let words = artifact.as_binary().to_vec();
+        assert!(Reflection::parse(artifact.as_binary_u8()).is_ok());
+
+        // Every instruction is given every smaller word count; none of them may panic.
+        let mut i = 5;
+        while i < words.len() {
+            let count = words[i] >> 16;
+            for shrunk in 1 .. count {
+                let mut broken = words.clone();
+                broken[i] = shrunk << 16 | (words[i] & 0xffff);
+                let bytes: Vec<u8> = broken.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect();
+                let _ = Reflection::parse(&bytes);
+            }
+            i += count as usize;
+        }
+    }
+
+    #[test]
+    fn compiles_the_shaders_that_use_includes() {
+        let shaders = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders");
+        for name in ["fractal.comp", "fractal_f64.comp", "deep_zoom.comp", "supersample_accumulate.comp",
+                     "supersample_resolve.comp"].iter() {
+            let path = shaders.join(name);
+            let source = fs::read_to_string(&path).unwrap();
+            let artifact = compile_glsl(&source, ShaderStage::Compute, &path.to_string_lossy())
+                .unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(Reflection::parse(artifact.as_binary_u8()).unwrap().stage, ShaderStage::Compute);
+        }
+    }
+
+    #[test]
+    fn resolves_includes_next_to_the_shader_first() {
+        let dir = std::env::temp_dir().join("vulkano_guide_include_test");
+        fs::create_dir_all(&dir).unwrap();
+        // Shadows `shaders/include/palette.glsl`.
+        fs::write(dir.join("palette.glsl"), "const uint LOCAL_SIZE = 4;\n").unwrap();
+
+        let source = "
+#version 450
+#extension GL_GOOGLE_include_directive : require
+#include \"palette.glsl\"
+layout(local_size_x = LOCAL_SIZE) in;
+void main() {}
+";
+        let file_name = dir.join("test.comp");
+        let artifact = compile_glsl(source, ShaderStage::Compute, &file_name.to_string_lossy()).unwrap();
+        assert_eq!(Reflection::parse(artifact.as_binary_u8()).unwrap().local_size, [4, 1, 1]);
+
+        let missing = source.replace("palette.glsl", "missing.glsl");
+        match compile_glsl(&missing, ShaderStage::Compute, &file_name.to_string_lossy()) {
+            Err(ShaderLoadError::Compile(log)) => assert!(log.contains("missing.glsl"), "{}", log),
+            _ => panic!("a missing include compiled"),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+// Shader stages as named by file extensions, `#pragma shader_stage(...)` lines and SPIR-V entry
+// points, and the `#include` resolution. build.rs includes this file through `#[path]`, so it
+// only depends on std and shaderc.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn from_extension(extension: &str) -> Option<ShaderStage> {
+        match extension {
+            "vert" => Some(ShaderStage::Vertex),
+            "tesc" => Some(ShaderStage::TessControl),
+            "tese" => Some(ShaderStage::TessEvaluation),
+            "geom" => Some(ShaderStage::Geometry),
+            "frag" => Some(ShaderStage::Fragment),
+            "comp" => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    pub fn from_pragma(name: &str) -> Option<ShaderStage> {
+        match name {
+            "vertex" => Some(ShaderStage::Vertex),
+            "tesscontrol" => Some(ShaderStage::TessControl),
+            "tesseval" => Some(ShaderStage::TessEvaluation),
+            "geometry" => Some(ShaderStage::Geometry),
+            "fragment" => Some(ShaderStage::Fragment),
+            "compute" => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    // Execution model of an OpEntryPoint.
+    pub fn from_execution_model(model: u32) -> Option<ShaderStage> {
+        match model {
+            0 => Some(ShaderStage::Vertex),
+            1 => Some(ShaderStage::TessControl),
+            2 => Some(ShaderStage::TessEvaluation),
+            3 => Some(ShaderStage::Geometry),
+            4 => Some(ShaderStage::Fragment),
+            5 => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    pub fn shaderc_kind(self) -> shaderc::ShaderKind {
+        match self {
+            ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+            ShaderStage::TessControl => shaderc::ShaderKind::TessControl,
+            ShaderStage::TessEvaluation => shaderc::ShaderKind::TessEvaluation,
+            ShaderStage::Geometry => shaderc::ShaderKind::Geometry,
+            ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+            ShaderStage::Compute => shaderc::ShaderKind::Compute,
+        }
+    }
+}
+
+// The stage named by a `#pragma shader_stage(...)` line, if any.
+pub fn pragma_stage(source: &str, path: &Path) -> Result<Option<ShaderStage>, String> {
+    for line in source.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("#pragma shader_stage(") {
+            let name = rest.trim_end().trim_end_matches(')').trim();
+            return ShaderStage::from_pragma(name)
+                .map(Some)
+                .ok_or_else(|| format!("{}: unknown shader stage `{}`", path.display(), name));
+        }
+    }
+    Ok(None)
+}
+
+// The stage of a GLSL file, from its extension or, for .glsl files, its pragma. Both must agree
+// when there are both.
+pub fn declared_stage(source: &str, path: &Path) -> Result<ShaderStage, String> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let pragma = pragma_stage(source, path)?;
+
+    match (ShaderStage::from_extension(extension), pragma) {
+        (Some(stage), Some(pragma)) if stage != pragma => {
+            Err(format!("{}: the extension says {:?} but the pragma says {:?}", path.display(), stage, pragma))
+        },
+        (Some(stage), _) => Ok(stage),
+        (None, Some(pragma)) if extension == "glsl" => Ok(pragma),
+        _ => Err(format!("{}: can't tell the stage, use a stage extension or `#pragma shader_stage(...)`",
+                         path.display())),
+    }
+}
+
+// Stages of the entry points in a SPIR-V module, given as host endian words.
+pub fn entry_point_stages(words: &[u32]) -> Vec<ShaderStage> {
+    let mut stages = Vec::new();
+    let mut i = 5;
+    while i < words.len() {
+        let count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xffff;
+        if count == 0 {
+            break;
+        }
+        // OpEntryPoint
+        if opcode == 15 && count > 1 && i + 1 < words.len() {
+            stages.extend(ShaderStage::from_execution_model(words[i + 1]));
+        }
+        i += count;
+    }
+    stages
+}
+
+// Compile options that resolve `#include "..."` relative to the including file and then in
+// `include_dir`, and `#include <...>` only in `include_dir`.
+pub fn compile_options(include_dir: PathBuf) -> Option<shaderc::CompileOptions<'static>> {
+    let mut options = shaderc::CompileOptions::new()?;
+    options.set_include_callback(move |requested, kind, requesting, _depth| {
+        let relative = Path::new(requesting).parent().unwrap_or_else(|| Path::new("")).join(requested);
+        let candidates = match kind {
+            shaderc::IncludeType::Relative => vec![relative, include_dir.join(requested)],
+            shaderc::IncludeType::Standard => vec![include_dir.join(requested)],
+        };
+
+        candidates.into_iter()
+            .find(|path| path.is_file())
+            .ok_or_else(|| format!("can't find `{}` included from {}", requested, requesting))
+            .and_then(|path| {
+                let content = fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+                Ok(shaderc::ResolvedInclude { content, resolved_name: path.to_string_lossy().into_owned() })
+            })
+    });
+    Some(options)
+}
@@ -0,0 +1,2 @@
+// Modules generated by build.rs from the files in `shaders/`.
+include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
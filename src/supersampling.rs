@@ -0,0 +1,143 @@
+use vulkano::device::{Device,Queue};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::{PipelineLayout,PipelineLayoutAbstract};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions,StorageImage};
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::cache::PipelineCache;
+
+use crate::cli::Args;
+use crate::shaders::{supersample_accumulate_comp as accumulate_cs, supersample_resolve_comp as resolve_cs};
+
+use std::sync::Arc;
+
+// Sample positions inside each pixel, as offsets from its top left corner in [0, 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Supersampling {
+    // One sample at the pixel center.
+    Off,
+    // n x n samples at the centers of an n x n grid.
+    Grid(u32),
+    // n x n samples, each at a random position inside its cell of the grid.
+    Jittered(u32),
+}
+
+impl Default for Supersampling {
+    fn default() -> Supersampling {
+        Supersampling::Off
+    }
+}
+
+impl Supersampling {
+    // Reads `--supersampling n` for n x n samples per pixel, jittered with `--jitter`.
+    pub fn from_args(args: &Args) -> Supersampling {
+        match args.value::<u32>("--supersampling") {
+            None | Some(0) | Some(1) => Supersampling::Off,
+            Some(n) if args.flag("--jitter") => Supersampling::Jittered(n),
+            Some(n) => Supersampling::Grid(n),
+        }
+    }
+
+    pub fn offsets(&self) -> Vec<[f32; 2]> {
+        let (n, jitter) = match *self {
+            Supersampling::Off => return vec![[0.5, 0.5]],
+            Supersampling::Grid(n) => (n, false),
+            Supersampling::Jittered(n) => (n, true),
+        };
+
+        // xorshift with a fixed seed, so renders are reproducible and tiles match each other.
+        let mut state = 0x9e37_79b9u32;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state >> 8) as f32 / (1u32 << 24) as f32
+        };
+
+        let mut offsets = Vec::with_capacity((n * n) as usize);
+        for y in 0 .. n {
+            for x in 0 .. n {
+                let (jx, jy) = if jitter { (random(), random()) } else { (0.5, 0.5) };
+                offsets.push([(x as f32 + jx) / n as f32, (y as f32 + jy) / n as f32]);
+            }
+        }
+        offsets
+    }
+}
+
+// Renders an image several times with different sample offsets and averages the results in
+// linear space. Works with any generator that renders into an rgba8 storage image and can shift
+// its sample position inside the pixel.
+pub struct Supersampler {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    accumulate: Arc<ComputePipeline<PipelineLayout<accumulate_cs::Layout>>>,
+    resolve: Arc<ComputePipeline<PipelineLayout<resolve_cs::Layout>>>,
+}
+
+impl Supersampler {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, cache: Option<Arc<PipelineCache>>) -> Supersampler {
+        let shader = accumulate_cs::Shader::load(device.clone()).expect("failed to create shader module");
+        let accumulate = Arc::new(
+            ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), cache.clone())
+                    .expect("failed to create compute pipeline"));
+
+        let shader = resolve_cs::Shader::load(device.clone()).expect("failed to create shader module");
+        let resolve = Arc::new(
+            ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), cache)
+                    .expect("failed to create compute pipeline"));
+
+        Supersampler { device, queue, accumulate, resolve }
+    }
+
+    // Records `render_sample` once per sample offset and the passes that average the samples
+    // into `output`. Without supersampling `render_sample` renders straight into `output`.
+    pub fn record<F>(&self, builder: &mut AutoCommandBufferBuilder, output: Arc<StorageImage<Format>>,
+                     supersampling: Supersampling, mut render_sample: F)
+        where F: FnMut(&mut AutoCommandBufferBuilder, Arc<StorageImage<Format>>, [f32; 2])
+    {
+        if supersampling == Supersampling::Off {
+            render_sample(builder, output, [0.5, 0.5]);
+            return;
+        }
+
+        let (width, height) = (output.dimensions().width(), output.dimensions().height());
+        let dimensions = Dimensions::Dim2d { width, height };
+        let sample = StorageImage::new(self.device.clone(), dimensions, Format::R8G8B8A8Unorm,
+                                       Some(self.queue.family())).unwrap();
+        let accumulation = StorageImage::new(self.device.clone(), dimensions, Format::R32G32B32A32Sfloat,
+                                             Some(self.queue.family())).unwrap();
+
+        let groups = [(width + 15) / 16, (height + 15) / 16, 1];
+
+        let layout = self.accumulate.layout().descriptor_set_layout(0).unwrap();
+        let set = Arc::new(
+            PersistentDescriptorSet::start(layout.clone())
+            .add_image(sample.clone()).unwrap()
+            .add_image(accumulation.clone()).unwrap()
+            .build().unwrap(),
+        );
+
+        let offsets = supersampling.offsets();
+        for (i, &offset) in offsets.iter().enumerate() {
+            render_sample(builder, sample.clone(), offset);
+
+            let push_constants = accumulate_cs::ty::Params { first: (i == 0) as u32 };
+            builder
+                .dispatch(groups, self.accumulate.clone(), set.clone(), push_constants).unwrap();
+        }
+
+        let layout = self.resolve.layout().descriptor_set_layout(0).unwrap();
+        let set = Arc::new(
+            PersistentDescriptorSet::start(layout.clone())
+            .add_image(accumulation).unwrap()
+            .add_image(output).unwrap()
+            .build().unwrap(),
+        );
+
+        let push_constants = resolve_cs::ty::Params { weight: 1.0 / offsets.len() as f32 };
+        builder
+            .dispatch(groups, self.resolve.clone(), set, push_constants).unwrap();
+    }
+}
@@ -0,0 +1,245 @@
+use vulkano::device::Queue;
+use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBuffer,AutoCommandBufferBuilder,CommandBuffer,CommandBufferExecFuture};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions,ImageLayout,ImageUsage,ImmutableImage,MipmapsCount,StorageImage};
+use vulkano::sync::NowFuture;
+
+use image::hdr::HdrDecoder;
+
+use crate::export::{linear_to_srgb, srgb_to_linear};
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+// How the texels of an uploaded image are stored. `Srgb` has the GPU decode to linear when
+// sampling, `Unorm` hands the stored values to the shader as they are and `Float` keeps the full
+// range of HDR files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Srgb,
+    Unorm,
+    Float,
+}
+
+impl TextureFormat {
+    pub fn from_name(name: &str) -> Option<TextureFormat> {
+        match name.to_lowercase().as_str() {
+            "srgb" => Some(TextureFormat::Srgb),
+            "unorm" => Some(TextureFormat::Unorm),
+            "float" => Some(TextureFormat::Float),
+            _ => None,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        match *self {
+            TextureFormat::Srgb => Format::R8G8B8A8Srgb,
+            TextureFormat::Unorm => Format::R8G8B8A8Unorm,
+            TextureFormat::Float => Format::R32G32B32A32Sfloat,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TexturePixels {
+    // sRGB encoded, as stored by PNG and JPEG.
+    U8(Vec<u8>),
+    // Linear, from Radiance HDR files.
+    F32(Vec<f32>),
+}
+
+// An image file decoded to RGBA, ready to upload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: TexturePixels,
+}
+
+impl TextureData {
+    // `.hdr` files keep their float samples, everything else `image` can decode becomes RGBA8.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<TextureData> {
+        let path = path.as_ref();
+        let to_io = |e: image::ImageError| io::Error::new(io::ErrorKind::Other, e);
+
+        let is_hdr = path.extension().and_then(|ext| ext.to_str()).map_or(false, |ext| ext.eq_ignore_ascii_case("hdr"));
+        if is_hdr {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?)).map_err(to_io)?;
+            let metadata = decoder.metadata();
+            let rgb = decoder.read_image_hdr().map_err(to_io)?;
+
+            let pixels = rgb.iter().flat_map(|p| vec![p[0], p[1], p[2], 1.0]).collect();
+            return Ok(TextureData { width: metadata.width, height: metadata.height, pixels: TexturePixels::F32(pixels) });
+        }
+
+        let image = image::open(path).map_err(to_io)?.to_rgba8();
+        Ok(TextureData { width: image.width(), height: image.height(), pixels: TexturePixels::U8(image.into_raw()) })
+    }
+
+    // Float for HDR files, sRGB for the rest.
+    pub fn default_format(&self) -> TextureFormat {
+        match self.pixels {
+            TexturePixels::U8(_) => TextureFormat::Srgb,
+            TexturePixels::F32(_) => TextureFormat::Float,
+        }
+    }
+
+    // Levels of a full mip chain, down to 1x1.
+    pub fn mip_levels(&self) -> u32 {
+        32 - self.width.max(self.height).leading_zeros()
+    }
+
+    // `levels` mip levels as tightly packed texels of `format`, each with its size. Levels past
+    // the first are box filtered in linear space, so sRGB textures don't darken as they shrink.
+    pub fn mip_chain(&self, format: TextureFormat, levels: u32) -> Vec<(u32, u32, Vec<u8>)> {
+        let mut chain = vec![(self.width, self.height, self.encode(format))];
+
+        let mut linear = self.to_linear(format);
+        let (mut width, mut height) = (self.width, self.height);
+        for _ in 1 .. levels {
+            let (next, next_width, next_height) = downsample(&linear, width, height);
+            chain.push((next_width, next_height, encode_linear(&next, format)));
+
+            linear = next;
+            width = next_width;
+            height = next_height;
+        }
+
+        chain
+    }
+
+    fn encode(&self, format: TextureFormat) -> Vec<u8> {
+        match (&self.pixels, format) {
+            (TexturePixels::U8(bytes), TextureFormat::Srgb) | (TexturePixels::U8(bytes), TextureFormat::Unorm) => bytes.clone(),
+            _ => encode_linear(&self.to_linear(format), format),
+        }
+    }
+
+    // RGBA in the space the texels of `format` are stored in once decoded: linear for `Srgb` and
+    // `Float`, the file's own values for `Unorm`.
+    fn to_linear(&self, format: TextureFormat) -> Vec<f32> {
+        match self.pixels {
+            TexturePixels::U8(ref bytes) => bytes.iter().enumerate().map(|(i, &b)| {
+                let c = b as f32 / 255.0;
+                if i % 4 == 3 || format == TextureFormat::Unorm { c } else { srgb_to_linear(c) }
+            }).collect(),
+            TexturePixels::F32(ref samples) => samples.clone(),
+        }
+    }
+}
+
+fn encode_linear(pixels: &[f32], format: TextureFormat) -> Vec<u8> {
+    let unorm8 = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+
+    match format {
+        TextureFormat::Srgb => pixels.iter().enumerate().map(|(i, &c)| {
+            if i % 4 == 3 { unorm8(c) } else { unorm8(linear_to_srgb(c.max(0.0).min(1.0))) }
+        }).collect(),
+        TextureFormat::Unorm => pixels.iter().map(|&c| unorm8(c)).collect(),
+        TextureFormat::Float => pixels.iter().flat_map(|c| c.to_le_bytes().to_vec()).collect(),
+    }
+}
+
+// Halves an RGBA image averaging 2x2 blocks. Odd edges reuse their last row or column.
+fn downsample(pixels: &[f32], width: u32, height: u32) -> (Vec<f32>, u32, u32) {
+    let next_width = (width / 2).max(1);
+    let next_height = (height / 2).max(1);
+
+    let texel = |x: u32, y: u32| {
+        let i = (y.min(height - 1) * width + x.min(width - 1)) as usize * 4;
+        &pixels[i .. i + 4]
+    };
+
+    let mut next = Vec::with_capacity((next_width * next_height) as usize * 4);
+    for y in 0 .. next_height {
+        for x in 0 .. next_width {
+            let block = [texel(2 * x, 2 * y), texel(2 * x + 1, 2 * y), texel(2 * x, 2 * y + 1), texel(2 * x + 1, 2 * y + 1)];
+            for c in 0 .. 4 {
+                next.push(block.iter().map(|t| t[c]).sum::<f32>() / 4.0);
+            }
+        }
+    }
+
+    (next, next_width, next_height)
+}
+
+// Uploads `data` into a sampled image, with a full mip chain if `mipmaps`. The image can be used
+// once the returned future has executed, so later work should be chained after it.
+pub fn upload(queue: Arc<Queue>, data: &TextureData, format: TextureFormat, mipmaps: bool)
+    -> (Arc<ImmutableImage<Format>>, CommandBufferExecFuture<NowFuture, AutoCommandBuffer>)
+{
+    let device = queue.device().clone();
+    let levels = if mipmaps { data.mip_levels() } else { 1 };
+
+    let usage = ImageUsage {
+        transfer_source: true,
+        transfer_destination: true,
+        sampled: true,
+        .. ImageUsage::none()
+    };
+    let (image, init) = ImmutableImage::uninitialized(device.clone(),
+                            Dimensions::Dim2d { width: data.width, height: data.height }, format.format(),
+                            MipmapsCount::Specific(levels), usage, ImageLayout::ShaderReadOnlyOptimal,
+                            Some(queue.family())).expect("failed to create image");
+    let init = Arc::new(init);
+
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    for (level, (width, height, texels)) in data.mip_chain(format, levels).into_iter().enumerate() {
+        let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false, texels.into_iter())
+                    .expect("failed to create buffer");
+        builder
+            .copy_buffer_to_image_dimensions(buf, init.clone(), [0, 0, 0], [width, height, 1], 0, 1, level as u32)
+            .unwrap();
+    }
+    let command_buffer = builder.build().unwrap();
+
+    (image, command_buffer.execute(queue).unwrap())
+}
+
+// Uploads `data` into a storage image, for compute shaders that read or modify it. Storage images
+// have a single mip level and can't use sRGB formats.
+pub fn upload_storage(queue: Arc<Queue>, data: &TextureData, format: TextureFormat)
+    -> (Arc<StorageImage<Format>>, CommandBufferExecFuture<NowFuture, AutoCommandBuffer>)
+{
+    assert!(format != TextureFormat::Srgb, "storage images can't use sRGB formats");
+    let device = queue.device().clone();
+
+    let image = StorageImage::new(device.clone(), Dimensions::Dim2d { width: data.width, height: data.height },
+                    format.format(), Some(queue.family())).unwrap();
+
+    let buf = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false,
+                    data.encode(format).into_iter()).expect("failed to create buffer");
+
+    let mut builder = AutoCommandBufferBuilder::new(device.clone(), queue.family()).unwrap();
+    builder
+        .copy_buffer_to_image(buf, image.clone())
+        .unwrap();
+    let command_buffer = builder.build().unwrap();
+
+    (image, command_buffer.execute(queue).unwrap())
+}
+
+// Opens and uploads `path`. Without a format, HDR files become float textures and the rest sRGB.
+pub fn load<P: AsRef<Path>>(queue: Arc<Queue>, path: P, format: Option<TextureFormat>, mipmaps: bool)
+    -> io::Result<(Arc<ImmutableImage<Format>>, CommandBufferExecFuture<NowFuture, AutoCommandBuffer>)>
+{
+    let data = TextureData::open(path)?;
+    let format = format.unwrap_or_else(|| data.default_format());
+    Ok(upload(queue, &data, format, mipmaps))
+}
+
+// Opens and uploads `path` into a storage image. Without a format, HDR files become float images
+// and the rest UNORM.
+pub fn load_storage<P: AsRef<Path>>(queue: Arc<Queue>, path: P, format: Option<TextureFormat>)
+    -> io::Result<(Arc<StorageImage<Format>>, CommandBufferExecFuture<NowFuture, AutoCommandBuffer>)>
+{
+    let data = TextureData::open(path)?;
+    let format = format.unwrap_or_else(|| match data.default_format() {
+        TextureFormat::Srgb => TextureFormat::Unorm,
+        format => format,
+    });
+    Ok(upload_storage(queue, &data, format))
+}
@@ -0,0 +1,134 @@
+use vulkano::device::Device;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// How an output image is split into tiles. Tiles are rendered one row at a time, so at most one
+// row of tiles (a strip of `width` x `tile_height` pixels) is held in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TilePlan {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+impl TilePlan {
+    // `memory_budget` is in bytes and covers the strip kept on the host plus the storage image
+    // and readback buffer of one tile, all RGBA8.
+    pub fn new(width: u32, height: u32, max_tile_size: u32, memory_budget: usize) -> TilePlan {
+        // At least 1, `columns` and `rows` divide by them.
+        let mut tile_width = width.min(max_tile_size).max(1);
+        let mut tile_height = height.min(max_tile_size).max(1);
+
+        let bytes = |tw: u32, th: u32| width as usize * th as usize * 4 + tw as usize * th as usize * 4 * 2;
+
+        while bytes(tile_width, tile_height) > memory_budget && tile_height > 1 {
+            tile_height = (tile_height + 1) / 2;
+        }
+        while bytes(tile_width, tile_height) > memory_budget && tile_width > 1 {
+            tile_width = (tile_width + 1) / 2;
+        }
+
+        TilePlan { width, height, tile_width, tile_height }
+    }
+
+    pub fn for_device(device: &Device, width: u32, height: u32, memory_budget: usize) -> TilePlan {
+        let max_tile_size = device.physical_device().limits().max_image_dimension_2d();
+        TilePlan::new(width, height, max_tile_size, memory_budget)
+    }
+
+    pub fn columns(&self) -> u32 {
+        (self.width + self.tile_width - 1) / self.tile_width
+    }
+
+    pub fn rows(&self) -> u32 {
+        (self.height + self.tile_height - 1) / self.tile_height
+    }
+
+    pub fn tiles_in_row(&self, row: u32) -> Vec<Tile> {
+        let y = row * self.tile_height;
+        let height = self.tile_height.min(self.height - y);
+
+        (0 .. self.columns())
+            .map(|column| {
+                let x = column * self.tile_width;
+                Tile { x, y, width: self.tile_width.min(self.width - x), height }
+            })
+            .collect()
+    }
+}
+
+// Calls `render_tile` for every tile, which must return the tile as tightly packed RGBA8 rows,
+// and streams the result into a PNG file row by row.
+pub fn render_to_png<P, F>(plan: &TilePlan, path: P, mut render_tile: F) -> io::Result<()>
+    where P: AsRef<Path>, F: FnMut(&Tile) -> Vec<u8>
+{
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, plan.width, plan.height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let mut stream = writer.stream_writer();
+
+    let row_bytes = plan.width as usize * 4;
+
+    for row in 0 .. plan.rows() {
+        let tiles = plan.tiles_in_row(row);
+        let strip_height = tiles[0].height as usize;
+        let mut strip = vec![0u8; row_bytes * strip_height];
+
+        for tile in tiles.iter() {
+            let pixels = render_tile(tile);
+            let tile_bytes = tile.width as usize * 4;
+            assert_eq!(pixels.len(), tile_bytes * tile.height as usize, "tile has the wrong size");
+
+            for (y, line) in pixels.chunks(tile_bytes).enumerate() {
+                let start = y * row_bytes + tile.x as usize * 4;
+                strip[start .. start + tile_bytes].copy_from_slice(line);
+            }
+        }
+
+        stream.write_all(&strip)?;
+        println!("Rendered tile row {} of {}", row + 1, plan.rows());
+    }
+
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_image() {
+        let plan = TilePlan::new(1000, 700, 256, usize::MAX);
+        assert_eq!((plan.columns(), plan.rows()), (4, 3));
+
+        let last = plan.tiles_in_row(2);
+        assert_eq!(last[3], Tile { x: 768, y: 512, width: 232, height: 188 });
+    }
+
+    #[test]
+    fn degenerate_sizes_have_no_empty_tiles() {
+        let plan = TilePlan::new(100, 100, 0, usize::MAX);
+        assert_eq!((plan.tile_width, plan.tile_height), (1, 1));
+        assert_eq!((plan.columns(), plan.rows()), (100, 100));
+
+        let plan = TilePlan::new(0, 0, 4096, 0);
+        assert_eq!((plan.columns(), plan.rows()), (0, 0));
+    }
+}
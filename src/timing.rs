@@ -0,0 +1,213 @@
+use vulkano::device::{Device,DeviceOwned,Queue};
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::{AutoCommandBufferBuilder,CommandBuffer,CommandBufferExecError};
+use vulkano::command_buffer::pool::CommandPool;
+use vulkano::command_buffer::pool::standard::StandardCommandPoolBuilder;
+use vulkano::command_buffer::sys::{Flags,Kind,KindOcclusionQuery,UnsafeCommandBuffer,UnsafeCommandBufferBuilder};
+use vulkano::image::{ImageAccess,ImageLayout};
+use vulkano::query::{QueryPipelineStatisticFlags,QueryType,UnsafeQueryPool};
+use vulkano::sync::{AccessCheckError,AccessFlags,GpuFuture,PipelineStages};
+use vulkano::VulkanObject;
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    pub name: String,
+    pub milliseconds: f64,
+}
+
+// Measures GPU time per pass with a timestamp query pool. Vulkano's AutoCommandBufferBuilder
+// can't record queries, so `begin` and `end` add tiny secondary command buffers that write a
+// BOTTOM_OF_PIPE timestamp; they run inside the pass's own command buffer, with nothing else
+// between them.
+pub struct GpuTimer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pool: UnsafeQueryPool,
+    period: f64,
+    // Bits of each timestamp that count, 0 when the queue can't write them at all.
+    valid_bits: u32,
+    passes: Vec<String>,
+    open: bool,
+}
+
+impl GpuTimer {
+    pub fn new(device: Arc<Device>, queue: Arc<Queue>, max_passes: u32) -> GpuTimer {
+        let physical = device.physical_device();
+        let limits = physical.limits();
+        if limits.timestamp_compute_and_graphics() == 0 {
+            println!("Warning: the device doesn't guarantee timestamps on every queue");
+        }
+
+        let valid_bits = unsafe {
+            let vk = physical.instance().pointers();
+            let mut count = 0;
+            vk.GetPhysicalDeviceQueueFamilyProperties(physical.internal_object(), &mut count, ptr::null_mut());
+            let mut families = Vec::with_capacity(count as usize);
+            vk.GetPhysicalDeviceQueueFamilyProperties(physical.internal_object(), &mut count, families.as_mut_ptr());
+            families.set_len(count as usize);
+            families[queue.family().id() as usize].timestampValidBits
+        };
+        if valid_bits == 0 {
+            println!("Warning: queue family {} doesn't support timestamps, timings will be zero", queue.family().id());
+        }
+
+        let pool = UnsafeQueryPool::new(device.clone(), QueryType::Timestamp, max_passes * 2)
+                    .expect("failed to create query pool");
+
+        GpuTimer {
+            period: limits.timestamp_period() as f64,
+            device,
+            queue,
+            pool,
+            valid_bits,
+            passes: Vec::new(),
+            open: false,
+        }
+    }
+
+    // Starts timing pass `name` at this point of `builder`, which must be outside a render pass
+    // and submitted to the timer's queue.
+    pub fn begin(&mut self, builder: &mut AutoCommandBufferBuilder, name: &str) {
+        assert!(!self.open, "`GpuTimer::begin` called twice without `end`");
+        let first = self.passes.len() as u32 * 2;
+        assert!(first + 1 < self.pool.num_slots(), "too many passes for this GpuTimer");
+
+        self.stamp(builder, first, true);
+        self.passes.push(name.to_owned());
+        self.open = true;
+    }
+
+    // Ends the pass started by the last `begin`. The timings can be read once the command
+    // buffer has finished executing.
+    pub fn end(&mut self, builder: &mut AutoCommandBufferBuilder) {
+        assert!(self.open, "`GpuTimer::end` called without `begin`");
+        let last = self.passes.len() as u32 * 2 - 1;
+
+        self.stamp(builder, last, false);
+        self.open = false;
+    }
+
+    fn stamp(&self, builder: &mut AutoCommandBufferBuilder, query: u32, reset: bool) {
+        if self.valid_bits == 0 {
+            return;
+        }
+
+        let vk = self.device.pointers();
+        let pool = self.pool.internal_object();
+        let timestamp = unsafe {
+            let alloc = Device::standard_command_pool(&self.device, self.queue.family())
+                            .alloc(true, 1).unwrap().next().unwrap();
+            let kind = Kind::secondary(KindOcclusionQuery::Forbidden, QueryPipelineStatisticFlags::none());
+            let raw = UnsafeCommandBufferBuilder::new(&alloc, kind, Flags::OneTimeSubmit).unwrap();
+            // Both queries of the pass are reset together, before the first one is written.
+            if reset {
+                vk.CmdResetQueryPool(raw.internal_object(), pool, query, 2);
+            }
+            vk.CmdWriteTimestamp(raw.internal_object(), vk_sys::PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT, pool, query);
+            Timestamp { device: self.device.clone(), command_buffer: raw.build().unwrap(), _alloc: alloc }
+        };
+
+        // The secondary command buffer only touches the query pool, which vulkano doesn't track.
+        unsafe {
+            builder.execute_commands(timestamp).unwrap();
+        }
+    }
+
+    pub fn report(&self) -> Vec<PassTiming> {
+        let count = self.passes.len() * 2;
+        if count == 0 {
+            return Vec::new();
+        }
+
+        if self.valid_bits == 0 {
+            return self.passes.iter().map(|name| PassTiming { name: name.clone(), milliseconds: 0.0 }).collect();
+        }
+
+        let mut ticks = vec![0u64; count];
+        unsafe {
+            let vk = self.device.pointers();
+            let result = vk.GetQueryPoolResults(
+                self.device.internal_object(),
+                self.pool.internal_object(),
+                0,
+                count as u32,
+                count * 8,
+                ticks.as_mut_ptr() as *mut _,
+                8,
+                vk_sys::QUERY_RESULT_64_BIT | vk_sys::QUERY_RESULT_WAIT_BIT,
+            );
+            assert_eq!(result, vk_sys::SUCCESS, "failed to read timestamp queries");
+        }
+
+        // Only the low `valid_bits` bits count, so the counter can wrap around between the two
+        // timestamps of a pass.
+        let mask = if self.valid_bits >= 64 { !0 } else { (1u64 << self.valid_bits) - 1 };
+
+        self.passes.iter().zip(ticks.chunks(2))
+            .map(|(name, pair)| PassTiming {
+                name: name.clone(),
+                milliseconds: (pair[1].wrapping_sub(pair[0]) & mask) as f64 * self.period / 1_000_000.0,
+            })
+            .collect()
+    }
+
+    pub fn print_report(&self) {
+        let report = self.report();
+        let total: f64 = report.iter().map(|pass| pass.milliseconds).sum();
+
+        for pass in report.iter() {
+            println!("{:<24} {:>10.3} ms", pass.name, pass.milliseconds);
+        }
+        println!("{:<24} {:>10.3} ms", "total", total);
+    }
+
+    pub fn export_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "pass,milliseconds")?;
+        for pass in self.report() {
+            writeln!(file, "{},{}", pass.name, pass.milliseconds)?;
+        }
+        Ok(())
+    }
+}
+
+// A recorded timestamp, executed from the pass's own command buffer.
+struct Timestamp {
+    device: Arc<Device>,
+    command_buffer: UnsafeCommandBuffer,
+    _alloc: StandardCommandPoolBuilder,
+}
+
+unsafe impl DeviceOwned for Timestamp {
+    fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+}
+
+unsafe impl CommandBuffer for Timestamp {
+    fn inner(&self) -> &UnsafeCommandBuffer {
+        &self.command_buffer
+    }
+
+    fn lock_submit(&self, _: &dyn GpuFuture, _: &Queue) -> Result<(), CommandBufferExecError> {
+        Ok(())
+    }
+
+    unsafe fn unlock(&self) {}
+
+    fn check_buffer_access(&self, _: &dyn BufferAccess, _: bool, _: &Queue)
+                           -> Result<Option<(PipelineStages, AccessFlags)>, AccessCheckError> {
+        Err(AccessCheckError::Unknown)
+    }
+
+    fn check_image_access(&self, _: &dyn ImageAccess, _: ImageLayout, _: bool, _: &Queue)
+                          -> Result<Option<(PipelineStages, AccessFlags)>, AccessCheckError> {
+        Err(AccessCheckError::Unknown)
+    }
+}
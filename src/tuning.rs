@@ -0,0 +1,97 @@
+use vulkano::device::Device;
+
+use crate::pipeline_cache::{cache_dir, device_key};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Remembers the fastest workgroup size per kernel and device in `autotune.txt` inside the
+// pipeline cache directory, one `device-key/kernel x y z` line per entry.
+pub struct Autotuner {
+    device: Arc<Device>,
+    path: PathBuf,
+    winners: HashMap<String, [u32; 3]>,
+}
+
+impl Autotuner {
+    pub fn load(device: Arc<Device>) -> Autotuner {
+        let path = cache_dir().join("autotune.txt");
+
+        let winners = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let key = fields.next()?.to_owned();
+                let mut size = [0u32; 3];
+                for value in size.iter_mut() {
+                    *value = fields.next()?.parse().ok()?;
+                }
+                Some((key, size))
+            })
+            .collect();
+
+        Autotuner { device, path, winners }
+    }
+
+    // Returns the remembered winner for `kernel` if it's still one of the candidates, otherwise
+    // calls `measure` (which should return milliseconds, e.g. from a GpuTimer) for every candidate
+    // the device can run and remembers the fastest one.
+    pub fn best<F>(&mut self, kernel: &str, candidates: &[[u32; 3]], mut measure: F) -> [u32; 3]
+        where F: FnMut([u32; 3]) -> f64
+    {
+        let key = format!("{}/{}", device_key(&self.device), kernel);
+
+        if let Some(winner) = self.winners.get(&key) {
+            if candidates.contains(winner) {
+                return *winner;
+            }
+        }
+
+        let limits = self.device.physical_device().limits();
+        let max_size = limits.max_compute_work_group_size();
+        let max_invocations = limits.max_compute_work_group_invocations();
+
+        let mut best: Option<([u32; 3], f64)> = None;
+        for &size in candidates {
+            let fits = size.iter().zip(max_size.iter()).all(|(s, m)| s <= m)
+                && size.iter().map(|&s| s as u64).product::<u64>() <= max_invocations as u64;
+            if !fits {
+                continue;
+            }
+
+            // The first run pays for pipeline creation and cold caches, keep the best of a few.
+            let time = (0 .. 3).map(|_| measure(size)).fold(f64::INFINITY, f64::min);
+            println!("Autotune {}: {:?} took {:.3} ms", kernel, size, time);
+
+            if best.map(|(_, t)| time < t).unwrap_or(true) {
+                best = Some((size, time));
+            }
+        }
+
+        let (winner, _) = best.expect("no workgroup size candidate fits this device");
+        self.winners.insert(key, winner);
+
+        if let Err(e) = self.save() {
+            println!("Failed to save autotune results: {}", e);
+        }
+
+        winner
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = String::new();
+        for (key, size) in self.winners.iter() {
+            contents.push_str(&format!("{} {} {} {}\n", key, size[0], size[1], size[2]));
+        }
+
+        fs::write(&self.path, contents)
+    }
+}
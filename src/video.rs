@@ -0,0 +1,250 @@
+use vulkano::device::Device;
+use vulkano::buffer::{BufferUsage,CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::pipeline_layout::{PipelineLayout,PipelineLayoutAbstract};
+use vulkano::image::{ImageAccess,ImageViewAccess};
+use vulkano::pipeline::ComputePipeline;
+use vulkano::pipeline::cache::PipelineCache;
+
+use crate::readback::ImageDownload;
+use crate::shaders::rgb_to_yuv420_comp as cs;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Arc;
+
+// Converts rgba8 frames to planar YUV 4:2:0 on the GPU, so only half the bytes of each frame
+// have to be read back.
+pub struct Yuv420Converter {
+    pipeline: Arc<ComputePipeline<PipelineLayout<cs::Layout>>>,
+    width: u32,
+    height: u32,
+    // Row lengths of the planes in the buffer, in uints of four samples.
+    luma_stride: u32,
+    chroma_stride: u32,
+    planes: Arc<CpuAccessibleBuffer<[u32]>>,
+}
+
+impl Yuv420Converter {
+    pub fn new(device: Arc<Device>, width: u32, height: u32, cache: Option<Arc<PipelineCache>>) -> Yuv420Converter {
+        let shader = cs::Shader::load(device.clone()).expect("failed to create shader module");
+        let pipeline = Arc::new(
+            ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), cache)
+                    .expect("failed to create compute pipeline"));
+
+        let luma_stride = (width + 3) / 4;
+        let chroma_stride = ((width + 1) / 2 + 3) / 4;
+        let len = luma_stride * height + 2 * chroma_stride * ((height + 1) / 2);
+
+        let iter = (0 .. len).map(|_| 0u32);
+        let planes = CpuAccessibleBuffer::from_iter(device, BufferUsage::all(), false, iter)
+                        .expect("failed to create buffer");
+
+        Yuv420Converter { pipeline, width, height, luma_stride, chroma_stride, planes }
+    }
+
+    // Records the conversion of `image`, an rgba8 image with the `storage` usage and the size
+    // given to `new`.
+    pub fn record<I>(&self, builder: &mut AutoCommandBufferBuilder, image: Arc<I>)
+        where I: ImageViewAccess + Send + Sync + 'static
+    {
+        let chroma_height = (self.height + 1) / 2;
+        let push_constants = cs::ty::PlaneLayout {
+            luma_stride: self.luma_stride,
+            chroma_stride: self.chroma_stride,
+            u_offset: self.luma_stride * self.height,
+            v_offset: self.luma_stride * self.height + self.chroma_stride * chroma_height,
+        };
+
+        let layout = self.pipeline.layout().descriptor_set_layout(0).unwrap();
+        let set = Arc::new(
+            PersistentDescriptorSet::start(layout.clone())
+            .add_image(image).unwrap()
+            .add_buffer(self.planes.clone()).unwrap()
+            .build().unwrap(),
+        );
+
+        let groups = [(self.luma_stride + 15) / 16, (self.height + 15) / 16, 1];
+        builder
+            .dispatch(groups, self.pipeline.clone(), set, push_constants).unwrap();
+    }
+
+    // The Y, U and V planes of the last recorded frame, without row padding. Only valid once the
+    // command buffer it was recorded into has finished.
+    pub fn read_frame(&self) -> Vec<u8> {
+        let content = self.planes.read().unwrap();
+        let bytes: Vec<u8> = content.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect();
+
+        let (chroma_width, chroma_height) = ((self.width + 1) / 2, (self.height + 1) / 2);
+        let luma_stride = self.luma_stride as usize * 4;
+        let chroma_stride = self.chroma_stride as usize * 4;
+        let u_start = luma_stride * self.height as usize;
+        let v_start = u_start + chroma_stride * chroma_height as usize;
+
+        let mut frame = Vec::with_capacity((self.width * self.height + 2 * chroma_width * chroma_height) as usize);
+        for row in bytes[.. u_start].chunks(luma_stride) {
+            frame.extend_from_slice(&row[.. self.width as usize]);
+        }
+        for plane in &[&bytes[u_start .. v_start], &bytes[v_start ..]] {
+            for row in plane.chunks(chroma_stride) {
+                frame.extend_from_slice(&row[.. chroma_width as usize]);
+            }
+        }
+        frame
+    }
+}
+
+// Uncompressed YUV 4:2:0 video that ffmpeg, mpv and most encoders read directly.
+pub struct Y4mWriter {
+    file: BufWriter<File>,
+    frame_len: usize,
+}
+
+impl Y4mWriter {
+    pub fn create<P: AsRef<Path>>(path: P, width: u32, height: u32, fps: u32) -> io::Result<Y4mWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        // C420jpeg only gives the chroma siting, readers assume limited range unless told otherwise.
+        writeln!(file, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg XCOLORRANGE=FULL", width, height, fps)?;
+
+        let frame_len = (width * height + 2 * ((width + 1) / 2) * ((height + 1) / 2)) as usize;
+        Ok(Y4mWriter { file, frame_len })
+    }
+
+    // `frame` holds the Y, U and V planes, as given by `Yuv420Converter::read_frame`.
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        assert_eq!(frame.len(), self.frame_len, "frame doesn't match the video size");
+        self.file.write_all(b"FRAME\n")?;
+        self.file.write_all(frame)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// A local ffmpeg process reading raw RGBA frames from its stdin and encoding them to whatever
+// the extension of the output asks for.
+pub struct FfmpegPipe {
+    child: Child,
+    stdin: ChildStdin,
+}
+
+impl FfmpegPipe {
+    pub fn available() -> bool {
+        Command::new("ffmpeg")
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    pub fn spawn<P: AsRef<Path>>(path: P, width: u32, height: u32, fps: u32) -> io::Result<FfmpegPipe> {
+        let mut child = Command::new("ffmpeg")
+            .args(&["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(&["-s", &format!("{}x{}", width, height), "-framerate", &fps.to_string(), "-i", "-"])
+            .args(&["-pix_fmt", "yuv420p"])
+            .arg(path.as_ref())
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        Ok(FfmpegPipe { child, stdin })
+    }
+
+    pub fn write_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        self.stdin.write_all(rgba)
+    }
+
+    // Closes the pipe and waits for ffmpeg to finish encoding.
+    pub fn finish(self) -> io::Result<()> {
+        let FfmpegPipe { mut child, stdin } = self;
+        drop(stdin);
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::new(io::ErrorKind::Other, format!("ffmpeg failed with {}", status)));
+        }
+        Ok(())
+    }
+}
+
+enum Sink {
+    Y4m(Y4mWriter, Yuv420Converter),
+    Ffmpeg(FfmpegPipe),
+}
+
+// Streams rendered frames into a video: `.y4m` outputs are written directly, anything else goes
+// through ffmpeg, falling back to a `.y4m` next to the output when ffmpeg isn't installed.
+pub struct VideoWriter {
+    device: Arc<Device>,
+    sink: Sink,
+    path: PathBuf,
+    pending: Option<ImageDownload>,
+}
+
+impl VideoWriter {
+    pub fn create<P: AsRef<Path>>(device: Arc<Device>, path: P, width: u32, height: u32, fps: u32,
+                                  cache: Option<Arc<PipelineCache>>) -> io::Result<VideoWriter> {
+        let mut path = path.as_ref().to_path_buf();
+        let is_y4m = path.extension().and_then(|ext| ext.to_str()).map_or(false, |ext| ext.eq_ignore_ascii_case("y4m"));
+
+        let use_ffmpeg = !is_y4m && FfmpegPipe::available();
+        if !is_y4m && !use_ffmpeg {
+            path.set_extension("y4m");
+            println!("ffmpeg not found, writing {} instead", path.display());
+        }
+
+        let sink = if use_ffmpeg {
+            Sink::Ffmpeg(FfmpegPipe::spawn(&path, width, height, fps)?)
+        } else {
+            Sink::Y4m(Y4mWriter::create(&path, width, height, fps)?,
+                      Yuv420Converter::new(device.clone(), width, height, cache))
+        };
+
+        Ok(VideoWriter { device, sink, path, pending: None })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Records the work that gets the next frame out of `image`: the YUV conversion for Y4M, a
+    // plain copy for ffmpeg. `image` must be rgba8 with the `storage` and `transfer_source` usages, like
+    // a `StorageImage`.
+    pub fn record<I>(&mut self, builder: &mut AutoCommandBufferBuilder, image: Arc<I>)
+        where I: ImageAccess + ImageViewAccess + Send + Sync + 'static
+    {
+        match self.sink {
+            Sink::Y4m(_, ref converter) => converter.record(builder, image),
+            Sink::Ffmpeg(_) => {
+                let download = ImageDownload::record(builder, self.device.clone(), image, 0, 0)
+                    .expect("video frames must be rgba8 images");
+                self.pending = Some(download);
+            },
+        }
+    }
+
+    // Writes the frame from the last `record`, once its command buffer has finished.
+    pub fn write_frame(&mut self) -> io::Result<()> {
+        match self.sink {
+            Sink::Y4m(ref mut writer, ref converter) => writer.write_frame(&converter.read_frame()),
+            Sink::Ffmpeg(ref mut pipe) => {
+                let download = self.pending.take().expect("no frame was recorded");
+                let rgba = download.read()?.to_dynamic_image().to_rgba8().into_raw();
+                pipe.write_frame(&rgba)
+            },
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self.sink {
+            Sink::Y4m(writer, _) => writer.finish(),
+            Sink::Ffmpeg(pipe) => pipe.finish(),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use vulkano::instance::Instance;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::PhysicalDevice;
+use vulkano::device::Device;
+use vulkano::device::DeviceExtensions;
+use vulkano::device::Features;
+use vulkano::device::Queue;
+
+use vulkano_guide::cpu_reference::{self, MAX_MISMATCH};
+use vulkano_guide::fractal::{Fractal, FractalRenderer, Precision, View};
+use vulkano_guide::palette::Coloring;
+
+use std::sync::Arc;
+
+const PRECISIONS: [Precision; 2] = [Precision::Single, Precision::Double];
+
+// A single pixel centered exactly on `c`.
+fn point(fractal: Fractal, c: [f64; 2]) -> View {
+    View { center: c, width: 1, height: 1, max_iterations: 100, .. View::new(fractal) }
+}
+
+#[test]
+fn interior_points_never_escape() {
+    for &precision in PRECISIONS.iter() {
+        // 0 is a fixed point and -2 lands on the fixed point 2 of z² - 2.
+        for &c in [[0.0, 0.0], [-2.0, 0.0], [-1.0, 0.0], [0.25, 0.0]].iter() {
+            let view = point(Fractal::Mandelbrot, c);
+            assert_eq!(cpu_reference::escape(&view, 0, 0, precision), None, "{:?} at {:?}", precision, c);
+        }
+    }
+}
+
+#[test]
+fn escapes_at_the_expected_iteration() {
+    // Orbits of 0 under z² + c, counted from n = 0 for z_1 until |z|² exceeds 256².
+    let cases = [
+        // 2, 6, 38, 1446
+        ([2.0, 0.0], 3, 1446.0),
+        // 1, 2, 5, 26, 677
+        ([1.0, 0.0], 4, 677.0),
+        // 0 + 2i, -4 + 2i, 12 - 14i, -52 - 334i
+        ([0.0, 2.0], 3, (52.0f32 * 52.0 + 334.0 * 334.0).sqrt()),
+    ];
+
+    for &precision in PRECISIONS.iter() {
+        for &(c, n, modulus) in cases.iter() {
+            let view = point(Fractal::Mandelbrot, c);
+            assert_eq!(cpu_reference::escape(&view, 0, 0, precision), Some((n, modulus)), "{:?} at {:?}", precision, c);
+        }
+    }
+}
+
+#[test]
+fn julia_sets_start_from_the_pixel() {
+    // With c = 0 the orbit of z is z^(2^n): 4, 16, 256, 65536.
+    let view = point(Fractal::Julia { c: [0.0, 0.0] }, [2.0, 0.0]);
+    assert_eq!(cpu_reference::escape(&view, 0, 0, Precision::Double), Some((3, 65536.0)));
+
+    let view = point(Fractal::Julia { c: [0.0, 0.0] }, [0.5, 0.0]);
+    assert_eq!(cpu_reference::escape(&view, 0, 0, Precision::Double), None);
+}
+
+#[test]
+fn interior_pixels_get_the_interior_color() {
+    let coloring = Coloring { interior: [1.0, 0.5, 0.0], .. Coloring::default() };
+
+    let image = cpu_reference::render(&point(Fractal::Mandelbrot, [0.0, 0.0]), &coloring, Precision::Single);
+    assert_eq!(image.get_pixel(0, 0).0, [255, 128, 0, 255]);
+
+    let image = cpu_reference::render(&point(Fractal::Mandelbrot, [2.0, 0.0]), &coloring, Precision::Single);
+    assert_ne!(image.get_pixel(0, 0).0, [255, 128, 0, 255]);
+}
+
+// None when there's no Vulkan implementation or no device with a compute queue.
+fn compute_device() -> Option<(Arc<Device>, Arc<Queue>)> {
+    let instance = Instance::new(None, &InstanceExtensions::none(), None).ok()?;
+    let physical = PhysicalDevice::enumerate(&instance).next()?;
+    let queue_family = physical.queue_families().find(|&q| q.supports_compute())?;
+
+    let device_ext = DeviceExtensions {
+        khr_storage_buffer_storage_class: true,
+        .. DeviceExtensions::none()
+    };
+    let features = Features {
+        shader_float64: physical.supported_features().shader_float64,
+        .. Features::none()
+    };
+
+    let (device, mut queues) = Device::new(physical, &features, &device_ext,
+                                           [(queue_family, 1.0)].iter().cloned()).ok()?;
+    Some((device, queues.next().unwrap()))
+}
+
+#[test]
+fn gpu_matches_cpu_reference() {
+    let (device, queue) = match compute_device() {
+        Some(device) => device,
+        None => {
+            println!("No Vulkan device available, skipping the GPU comparison");
+            return;
+        },
+    };
+
+    let mut precisions = vec![Precision::Single];
+    if device.enabled_features().shader_float64 {
+        precisions.push(Precision::Double);
+    }
+
+    let cases = [
+        ("mandelbrot", View::new(Fractal::Mandelbrot)),
+        ("julia", View::new(Fractal::Julia { c: [-0.8, 0.156] })),
+        ("burning ship", View::new(Fractal::BurningShip)),
+        ("tricorn", View::new(Fractal::Tricorn)),
+        ("multibrot", View::new(Fractal::Multibrot { exponent: 3.0 })),
+    ];
+    let coloring = Coloring::default();
+
+    for &precision in precisions.iter() {
+        let mut renderer = FractalRenderer::new(device.clone(), queue.clone(), [16, 16, 1], precision, None);
+        renderer.set_coloring(coloring.clone());
+
+        for (name, view) in cases.iter() {
+            let view = View { width: 256, height: 256, .. *view };
+
+            let gpu = renderer.render(&view);
+            let cpu = cpu_reference::render(&view, &coloring, precision);
+
+            let (mismatched, _) = cpu_reference::compare(&gpu, &cpu);
+            let fraction = mismatched as f64 / (view.width * view.height) as f64;
+            assert!(fraction <= MAX_MISMATCH, "{} ({:?}): {:.3}% of the pixels differ", name, precision, fraction * 100.0);
+        }
+    }
+}